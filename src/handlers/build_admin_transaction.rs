@@ -0,0 +1,162 @@
+use crate::handlers::finish_transaction;
+use crate::utils::*;
+use crate::{BuildTransactionResponse, RewarderWrapper};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use tracing::Level;
+
+/// Build an unsigned transaction containing one or more rewarder/quarry admin actions. The
+/// signer is checked against the rewarder's authority, pause authority or pending authority
+/// depending on the action.
+pub async fn build_admin_transaction_handler(
+    Json(payload): Json<BuildAdminTransactionRequest>,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let signer = payload.signer;
+    let rewarder_key = payload.rewarder;
+    let fee_payer = payload.fee_payer.unwrap_or(signer);
+
+    if payload.actions.is_empty() {
+        tracing::event!(Level::ERROR, "Admin transaction request has no actions");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String("At least one action must be set".to_string())),
+        ));
+    }
+
+    let rewarder = match fetch_account(&network, &rewarder_key) {
+        Ok(value) => match RewarderWrapper::wrap(&value) {
+            Ok(value) => value,
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Rewarder wrap failed - Step 1/3");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Rewarder".to_string())),
+                ));
+            }
+        },
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Rewarder fetch successful - Step 1/3");
+
+    let mut instructions = Vec::with_capacity(payload.actions.len());
+
+    for action in payload.actions.iter() {
+        let required = action.required_signer(&rewarder);
+
+        if required != signer {
+            tracing::event!(Level::ERROR, "Signer does not match {:?}", action.role());
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(format!(
+                    "Signer {} is not the rewarder {} ({})",
+                    signer,
+                    action.role(),
+                    required
+                ))),
+            ));
+        }
+
+        instructions.push(action.to_instruction(&signer, &fee_payer, &rewarder_key));
+    }
+
+    tracing::event!(Level::INFO, "Instruction building successful - Step 2/3");
+
+    finish_transaction(payload.network_config, instructions, &fee_payer)
+}
+
+/// A single admin action on a rewarder or one of its quarries
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AdminAction {
+    SetAnnualRewards { new_rate: u64 },
+    SetRewardsShare { quarry: Pubkey, new_share: u64 },
+    SetFamine { quarry: Pubkey, famine_ts: i64 },
+    CreateQuarry { token_mint: Pubkey },
+    Pause,
+    Unpause,
+    TransferAuthority { new_authority: Pubkey },
+    AcceptAuthority,
+}
+
+impl AdminAction {
+    /// Name of the rewarder field the signer must match for this action
+    pub fn role(&self) -> &'static str {
+        match self {
+            Self::Pause | Self::Unpause => "pause_authority",
+            Self::AcceptAuthority => "pending_authority",
+            _ => "authority",
+        }
+    }
+
+    /// The key that has to sign this action according to the rewarder's current state
+    pub fn required_signer(&self, rewarder: &RewarderWrapper) -> Pubkey {
+        match self {
+            Self::Pause | Self::Unpause => rewarder.pause_authority,
+            Self::AcceptAuthority => rewarder.pending_authority,
+            _ => rewarder.authority,
+        }
+    }
+
+    pub fn to_instruction(
+        &self,
+        signer: &Pubkey,
+        fee_payer: &Pubkey,
+        rewarder: &Pubkey,
+    ) -> NamedInstruction {
+        match self {
+            Self::SetAnnualRewards { new_rate } => {
+                set_annual_rewards_ix(signer, rewarder, *new_rate)
+            }
+            Self::SetRewardsShare { quarry, new_share } => {
+                set_rewards_share_ix(signer, rewarder, quarry, *new_share)
+            }
+            Self::SetFamine { quarry, famine_ts } => {
+                set_famine_ix(signer, rewarder, quarry, *famine_ts)
+            }
+            Self::CreateQuarry { token_mint } => {
+                create_quarry_ix(signer, fee_payer, rewarder, token_mint)
+            }
+            Self::Pause => pause_ix(signer, rewarder),
+            Self::Unpause => unpause_ix(signer, rewarder),
+            Self::TransferAuthority { new_authority } => {
+                transfer_authority_ix(signer, rewarder, new_authority)
+            }
+            Self::AcceptAuthority => accept_authority_ix(signer, rewarder),
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "signer": [...], ~ the rewarder authority (or pause/pending authority), e.g. a multisig
+///     "rewarder": [...],
+///     "fee_payer": [...], ~ optional, defaults to signer
+///     "actions": [
+///         { "SetRewardsShare": { "quarry": [...], "new_share": 100 } },
+///         { "SetRewardsShare": { "quarry": [...], "new_share": 250 } },
+///         "Pause"
+///     ]
+/// }
+///
+/// Every action in a request must be signable by the same signer.
+#[derive(Serialize, Deserialize)]
+pub struct BuildAdminTransactionRequest {
+    pub network_config: NetworkConfig,
+    pub signer: Pubkey,
+    pub rewarder: Pubkey,
+    pub fee_payer: Option<Pubkey>,
+    pub actions: Vec<AdminAction>,
+}
@@ -0,0 +1,316 @@
+use crate::utils::*;
+use crate::{QuarryWrapper, RewarderWrapper};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use tracing::Level;
+
+/// Which user flow to build a transaction for
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UserTransactionKind {
+    CreateMiner,
+    Stake,
+    Withdraw,
+    Claim,
+}
+
+/// Build an unsigned transaction that creates the wallet's miner for a quarry
+pub async fn build_create_miner_handler(
+    Json(payload): Json<BuildUserTransactionRequest>,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    build_user_transaction(payload, UserTransactionKind::CreateMiner)
+}
+
+/// Build an unsigned transaction that stakes tokens into a quarry, creating the miner first
+/// if it doesn't exist yet
+pub async fn build_stake_handler(
+    Json(payload): Json<BuildUserTransactionRequest>,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    build_user_transaction(payload, UserTransactionKind::Stake)
+}
+
+/// Build an unsigned transaction that withdraws staked tokens from a quarry
+pub async fn build_withdraw_handler(
+    Json(payload): Json<BuildUserTransactionRequest>,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    build_user_transaction(payload, UserTransactionKind::Withdraw)
+}
+
+/// Build an unsigned transaction that claims the wallet's rewards from a quarry
+pub async fn build_claim_handler(
+    Json(payload): Json<BuildUserTransactionRequest>,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    build_user_transaction(payload, UserTransactionKind::Claim)
+}
+
+fn build_user_transaction(
+    payload: BuildUserTransactionRequest,
+    kind: UserTransactionKind,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let wallet = payload.wallet;
+    let quarry_key = payload.quarry;
+
+    let amount = match (kind, payload.amount) {
+        (UserTransactionKind::Stake, None) | (UserTransactionKind::Withdraw, None) => {
+            tracing::event!(Level::ERROR, "Transaction request is missing an amount");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String("amount must be set".to_string())),
+            ));
+        }
+        (_, amount) => amount.unwrap_or(0),
+    };
+
+    let quarry = match fetch_account(&network, &quarry_key) {
+        Ok(value) => match QuarryWrapper::wrap(&value) {
+            Ok(value) => value,
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Quarry wrap failed - Step 1/3");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Quarry".to_string())),
+                ));
+            }
+        },
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Quarry fetch successful - Step 1/3");
+
+    let (miner_key, _bump) = find_miner_address(&quarry_key, &wallet);
+    let miner_vault = find_miner_vault_address(&miner_key, &quarry.token_mint_key);
+    let token_account = payload
+        .token_account
+        .unwrap_or_else(|| get_associated_token_address(&wallet, &quarry.token_mint_key));
+
+    let mut instructions = Vec::new();
+
+    // Accounts that don't exist yet are created up front so the transaction works for a
+    // wallet that has never touched the quarry
+    let miner_exists = account_exists(&network, &miner_key)?;
+
+    if kind == UserTransactionKind::CreateMiner && miner_exists {
+        tracing::event!(Level::ERROR, "Miner already exists");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String("Miner already exists".to_string())),
+        ));
+    }
+
+    if kind == UserTransactionKind::CreateMiner || kind == UserTransactionKind::Stake {
+        if !miner_exists {
+            if !account_exists(&network, &miner_vault)? {
+                instructions.push(create_associated_token_account_ix(
+                    &wallet,
+                    &miner_key,
+                    &quarry.token_mint_key,
+                ));
+            }
+
+            instructions.push(create_miner_ix(
+                &wallet,
+                &wallet,
+                &quarry_key,
+                &quarry.rewarder_key,
+                &quarry.token_mint_key,
+            ));
+        }
+    } else if !miner_exists {
+        tracing::event!(Level::ERROR, "Miner does not exist");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String("Miner does not exist".to_string())),
+        ));
+    }
+
+    // Withdrawals pay out to the staked token account. Only the default associated token
+    // account can be created on the wallet's behalf. Claims take it as an unchecked
+    // placeholder, so it doesn't need to exist for them.
+    if kind == UserTransactionKind::Withdraw
+        && payload.token_account.is_none()
+        && !account_exists(&network, &token_account)?
+    {
+        instructions.push(create_associated_token_account_ix(
+            &wallet,
+            &wallet,
+            &quarry.token_mint_key,
+        ));
+    }
+
+    match kind {
+        UserTransactionKind::CreateMiner => {}
+        UserTransactionKind::Stake => instructions.push(stake_tokens_ix(
+            &wallet,
+            &quarry_key,
+            &quarry.rewarder_key,
+            &quarry.token_mint_key,
+            &token_account,
+            amount,
+        )),
+        UserTransactionKind::Withdraw => instructions.push(withdraw_tokens_ix(
+            &wallet,
+            &quarry_key,
+            &quarry.rewarder_key,
+            &quarry.token_mint_key,
+            &token_account,
+            amount,
+        )),
+        UserTransactionKind::Claim => {
+            let rewarder = match fetch_account(&network, &quarry.rewarder_key) {
+                Ok(value) => match RewarderWrapper::wrap(&value) {
+                    Ok(value) => value,
+                    Err(_e) => {
+                        tracing::event!(Level::ERROR, "Rewarder wrap failed - Step 2/3");
+                        return Err((
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            Json(Value::String("Failed to wrap Rewarder".to_string())),
+                        ));
+                    }
+                },
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Rewarder fetch failed - Step 2/3");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to fetch rewarder".to_string())),
+                    ));
+                }
+            };
+
+            let rewards_token_account =
+                get_associated_token_address(&wallet, &rewarder.rewards_token_mint);
+
+            if !account_exists(&network, &rewards_token_account)? {
+                instructions.push(create_associated_token_account_ix(
+                    &wallet,
+                    &wallet,
+                    &rewarder.rewards_token_mint,
+                ));
+            }
+
+            instructions.push(claim_rewards_ix(
+                &wallet,
+                &quarry_key,
+                &quarry.token_mint_key,
+                &token_account,
+                &rewards_token_account,
+                &ClaimRewarderAccounts {
+                    rewarder: quarry.rewarder_key,
+                    mint_wrapper: rewarder.mint_wrapper,
+                    rewards_token_mint: rewarder.rewards_token_mint,
+                    claim_fee_token_account: rewarder.claim_fee_token_account,
+                },
+            ));
+        }
+    }
+
+    tracing::event!(Level::INFO, "Instruction building successful - Step 2/3");
+
+    finish_transaction(payload.network_config, instructions, &wallet)
+}
+
+/// Checks whether the account exists. Only a missing account counts as absent, any other
+/// failure to read it fails the request.
+fn account_exists(
+    network: &Network,
+    account_pubkey: &Pubkey,
+) -> Result<bool, (StatusCode, Json<serde_json::Value>)> {
+    match fetch_account_if_exists(network, account_pubkey) {
+        Ok(value) => Ok(value.is_some()),
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 2/3");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ))
+        }
+    }
+}
+
+/// Attaches a recent blockhash to the instructions and encodes the unsigned transaction.
+/// Shared by every transaction builder endpoint.
+pub fn finish_transaction(
+    network_config: NetworkConfig,
+    instructions: Vec<NamedInstruction>,
+    fee_payer: &Pubkey,
+) -> Result<Json<BuildTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let recent_blockhash = match fetch_recent_blockhash(&network_config.variant) {
+        Ok(value) => value,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Blockhash fetch failed - Step 3/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String(
+                    "Failed to fetch recent blockhash".to_string(),
+                )),
+            ));
+        }
+    };
+
+    match UnsignedTransaction::build(instructions, fee_payer, recent_blockhash) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Transaction building successful - Step 3/3");
+            Ok(Json(BuildTransactionResponse {
+                network_config,
+                transaction: value,
+            }))
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Transaction serialization failed - Step 3/3");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to serialize transaction".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "wallet": [...], ~ the miner authority, also used as fee payer
+///     "quarry": [...],
+///     "amount": 1000000, ~ required for stake and withdraw, ignored otherwise
+///     "token_account": [...] ~ optional, defaults to the wallet's associated token account
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct BuildUserTransactionRequest {
+    pub network_config: NetworkConfig,
+    pub wallet: Pubkey,
+    pub quarry: Pubkey,
+    pub amount: Option<u64>,
+    pub token_account: Option<Pubkey>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "transaction": {
+///         "transaction": "AQAAAA...", ~ base64 encoded unsigned transaction
+///         "fee_payer": [...],
+///         "recent_blockhash": "5Tx8...",
+///         "instructions": [...] ~ program, name, named accounts and args of each instruction
+///     }
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct BuildTransactionResponse {
+    pub network_config: NetworkConfig,
+    pub transaction: UnsignedTransaction,
+}
@@ -0,0 +1,117 @@
+use crate::utils::*;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use tracing::Level;
+
+/// Fetch a transaction by signature and decode every Quarry instruction and event in it
+pub async fn decode_transaction_handler(
+    Json(payload): Json<DecodeTransactionRequest>,
+) -> Result<Json<DecodeTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+
+    let signature = match Signature::from_str(&payload.signature) {
+        Ok(value) => value,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Signature parse failed");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "signature must be a base58 string".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let transaction = fetch_transaction(&network, &signature);
+
+    match transaction {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Transaction fetch successful - Step 1/2");
+            let try_decode = DecodedTransaction::decode(&signature, &value);
+
+            match try_decode {
+                Some(value) => {
+                    tracing::event!(Level::INFO, "Transaction decode successful - Step 2/2");
+                    Ok(Json(DecodeTransactionResponse {
+                        network_config: payload.network_config,
+                        transaction: value,
+                    }))
+                }
+
+                None => {
+                    tracing::event!(Level::ERROR, "Transaction decode failed - Step 2/2");
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to decode transaction".to_string())),
+                    ))
+                }
+            }
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Transaction fetch failed - Step 1/2");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch transaction".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "signature": "5j7s..." ~ base58 transaction signature
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DecodeTransactionRequest {
+    pub network_config: NetworkConfig,
+    pub signature: String,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "transaction": {
+///         "signature": "5j7s...",
+///         "slot": 108000000,
+///         "block_time": 1637000000,
+///         "success": true,
+///         "err": null,
+///         "instructions": [
+///             {
+///                 "index": "1",
+///                 "program": "quarry_mine",
+///                 "program_id": [...],
+///                 "name": "stake_tokens",
+///                 "accounts": [{ "name": "authority", "pubkey": [...] }, ...],
+///                 "args": { "amount": 1000000 }
+///             }
+///         ],
+///         "events": [
+///             {
+///                 "program": "quarry_mine",
+///                 "program_id": [...],
+///                 "name": "StakeEvent",
+///                 "data": { "authority": [...], "token": [...], "amount": 1000000, ... }
+///             }
+///         ],
+///         "logs": [...]
+///     }
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct DecodeTransactionResponse {
+    pub network_config: NetworkConfig,
+    pub transaction: DecodedTransaction,
+}
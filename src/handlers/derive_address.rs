@@ -0,0 +1,319 @@
+use crate::utils::*;
+use crate::{MinerWrapper, QuarryWrapper, RewarderWrapper};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use tracing::Level;
+
+/// Derive the quarry address for a rewarder and staked token mint, checking the derived bump
+/// against the stored one if the quarry exists
+pub async fn derive_quarry_handler(
+    Json(payload): Json<DeriveQuarryRequest>,
+) -> Result<Json<DeriveAddressResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let (address, bump) = find_quarry_address(&payload.rewarder, &payload.token_mint);
+
+    let stored_bump = match fetch_account_if_exists(&network, &address) {
+        Ok(Some(account)) => match QuarryWrapper::wrap(&account) {
+            Ok(value) => Some(value.bump),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Quarry wrap failed");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Quarry".to_string())),
+                ));
+            }
+        },
+        Ok(None) => None,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Quarry address derivation successful");
+    Ok(Json(DeriveAddressResponse::new(
+        payload.network_config,
+        address,
+        bump,
+        stored_bump,
+    )))
+}
+
+/// Derive the miner address for a quarry and authority, checking the derived bump against the
+/// stored one if the miner exists
+pub async fn derive_miner_handler(
+    Json(payload): Json<DeriveMinerRequest>,
+) -> Result<Json<DeriveAddressResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let (address, bump) = find_miner_address(&payload.quarry, &payload.authority);
+
+    let stored_bump = match fetch_account_if_exists(&network, &address) {
+        Ok(Some(account)) => match MinerWrapper::wrap(&account) {
+            Ok(value) => Some(value.bump),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Miner wrap failed");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Miner".to_string())),
+                ));
+            }
+        },
+        Ok(None) => None,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Miner address derivation successful");
+    Ok(Json(DeriveAddressResponse::new(
+        payload.network_config,
+        address,
+        bump,
+        stored_bump,
+    )))
+}
+
+/// Derive the rewarder address for a base key, checking the derived bump against the stored
+/// one if the rewarder exists
+pub async fn derive_rewarder_handler(
+    Json(payload): Json<DeriveRewarderRequest>,
+) -> Result<Json<DeriveAddressResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let (address, bump) = find_rewarder_address(&payload.base);
+
+    let stored_bump = match fetch_account_if_exists(&network, &address) {
+        Ok(Some(account)) => match RewarderWrapper::wrap(&account) {
+            Ok(value) => Some(value.bump),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Rewarder wrap failed");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Rewarder".to_string())),
+                ));
+            }
+        },
+        Ok(None) => None,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Rewarder address derivation successful");
+    Ok(Json(DeriveAddressResponse::new(
+        payload.network_config,
+        address,
+        bump,
+        stored_bump,
+    )))
+}
+
+/// Derive the token vault of the miner belonging to a quarry and authority. The staked token
+/// mint is read from the quarry account when it isn't supplied.
+pub async fn derive_miner_vault_handler(
+    Json(payload): Json<DeriveMinerVaultRequest>,
+) -> Result<Json<DeriveMinerVaultResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+
+    let token_mint = match payload.token_mint {
+        Some(value) => value,
+        None => match fetch_account(&network, &payload.quarry) {
+            Ok(value) => match QuarryWrapper::wrap(&value) {
+                Ok(value) => value.token_mint_key,
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Quarry wrap failed - Step 1/2");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to wrap Quarry".to_string())),
+                    ));
+                }
+            },
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Account fetch failed - Step 1/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch account".to_string())),
+                ));
+            }
+        },
+    };
+
+    tracing::event!(Level::INFO, "Token mint resolution successful - Step 1/2");
+
+    let (miner, _bump) = find_miner_address(&payload.quarry, &payload.authority);
+    let address = find_miner_vault_address(&miner, &token_mint);
+
+    let stored_vault = match fetch_account_if_exists(&network, &miner) {
+        Ok(Some(account)) => match MinerWrapper::wrap(&account) {
+            Ok(value) => Some(value.token_vault_key),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Miner wrap failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Miner".to_string())),
+                ));
+            }
+        },
+        Ok(None) => None,
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 2/2");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    tracing::event!(Level::INFO, "Miner vault derivation successful - Step 2/2");
+    Ok(Json(DeriveMinerVaultResponse {
+        network_config: payload.network_config,
+        miner,
+        token_mint,
+        address,
+        miner_exists: stored_vault.is_some(),
+        vault_matches: stored_vault.map(|stored| stored == address),
+    }))
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "rewarder": [...],
+///     "token_mint": [...]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeriveQuarryRequest {
+    pub network_config: NetworkConfig,
+    pub rewarder: Pubkey,
+    pub token_mint: Pubkey,
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry": [...],
+///     "authority": [...]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeriveMinerRequest {
+    pub network_config: NetworkConfig,
+    pub quarry: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "base": [...]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeriveRewarderRequest {
+    pub network_config: NetworkConfig,
+    pub base: Pubkey,
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry": [...],
+///     "authority": [...],
+///     "token_mint": [...] ~ optional, read from the quarry when omitted
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeriveMinerVaultRequest {
+    pub network_config: NetworkConfig,
+    pub quarry: Pubkey,
+    pub authority: Pubkey,
+    pub token_mint: Option<Pubkey>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "address": [...],
+///     "bump": 254,
+///     "account_exists": true,
+///     "stored_bump": 254, ~ null when the account doesn't exist
+///     "bump_matches": true ~ null when the account doesn't exist
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct DeriveAddressResponse {
+    pub network_config: NetworkConfig,
+    pub address: Pubkey,
+    pub bump: u8,
+    pub account_exists: bool,
+    pub stored_bump: Option<u8>,
+    pub bump_matches: Option<bool>,
+}
+
+impl DeriveAddressResponse {
+    pub fn new(
+        network_config: NetworkConfig,
+        address: Pubkey,
+        bump: u8,
+        stored_bump: Option<u8>,
+    ) -> Self {
+        Self {
+            network_config,
+            address,
+            bump,
+            account_exists: stored_bump.is_some(),
+            stored_bump,
+            bump_matches: stored_bump.map(|stored| stored == bump),
+        }
+    }
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "miner": [...],
+///     "token_mint": [...],
+///     "address": [...],
+///     "miner_exists": true,
+///     "vault_matches": true ~ null when the miner doesn't exist
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct DeriveMinerVaultResponse {
+    pub network_config: NetworkConfig,
+    pub miner: Pubkey,
+    pub token_mint: Pubkey,
+    pub address: Pubkey,
+    pub miner_exists: bool,
+    /// Whether the derived vault matches Miner::token_vault_key
+    pub vault_matches: Option<bool>,
+}
@@ -0,0 +1,270 @@
+use crate::utils::*;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use tracing::Level;
+
+/// Most signatures a single export request scans, continue with next_before for more
+pub const MAX_SIGNATURES_PER_REQUEST: usize = 1_000;
+
+/// Signatures scanned when the request doesn't specify max_signatures
+pub const DEFAULT_MAX_SIGNATURES: usize = MAX_SIGNATURES_PER_REQUEST;
+
+/// Claim transactions fetched at once while exporting
+pub const EXPORT_FETCH_CONCURRENCY: usize = 8;
+
+/// Output format of a claim export
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// List every reward claim made by a wallet within a date range, for accounting
+pub async fn export_claims_handler(
+    Json(payload): Json<ExportClaimsRequest>,
+) -> Result<Json<ExportClaimsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let wallet = payload.pubkey_config.pubkey;
+    let start_ts = payload.start_ts.unwrap_or(i64::MIN);
+    let end_ts = payload.end_ts.unwrap_or(i64::MAX);
+    let max_signatures = payload
+        .max_signatures
+        .unwrap_or(DEFAULT_MAX_SIGNATURES)
+        .clamp(1, MAX_SIGNATURES_PER_REQUEST);
+
+    let before = match payload.before.as_deref().map(Signature::from_str) {
+        None => None,
+        Some(Ok(value)) => Some(value),
+        Some(Err(_e)) => {
+            tracing::event!(Level::ERROR, "Signature cursor parse failed");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "before must be a base58 signature".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let signatures =
+        match fetch_signatures_for_address(&network, &wallet, before, None, Some(max_signatures)) {
+            Ok(value) => {
+                tracing::event!(Level::INFO, "Signatures fetch successful - Step 1/2");
+                value
+            }
+
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Signatures fetch failed - Step 1/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch signatures".to_string())),
+                ));
+            }
+        };
+
+    // Signatures come back newest first, so the scan is complete once it passes start_ts or
+    // the wallet has no older signatures
+    let mut claim_signatures = Vec::new();
+    let mut signatures_scanned: usize = 0;
+    let mut complete = signatures.len() < max_signatures;
+
+    for status in signatures.iter() {
+        let block_time = status.block_time.unwrap_or(i64::MAX);
+
+        if block_time < start_ts {
+            complete = true;
+            break;
+        }
+
+        signatures_scanned += 1;
+
+        if status.err.is_some() || status.block_time.is_none() || block_time > end_ts {
+            continue;
+        }
+
+        if let Ok(signature) = Signature::from_str(&status.signature) {
+            claim_signatures.push(signature);
+        }
+    }
+
+    // getTransaction calls are blocking, keep them off the async workers
+    let fetched = tokio::task::spawn_blocking(move || {
+        fetch_transactions(&network, &claim_signatures, EXPORT_FETCH_CONCURRENCY)
+            .map(|transactions| (claim_signatures, transactions))
+    })
+    .await;
+
+    let (claim_signatures, transactions) = match fetched {
+        Ok(Ok(value)) => value,
+        _ => {
+            tracing::event!(Level::ERROR, "Transaction fetch failed - Step 2/2");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch transaction".to_string())),
+            ));
+        }
+    };
+
+    let mut rows: Vec<ClaimRow> = claim_signatures
+        .iter()
+        .zip(transactions.iter())
+        .filter_map(|(signature, tx)| DecodedTransaction::decode(signature, tx))
+        .flat_map(|tx| ActivityEntry::from_transaction(&tx))
+        .filter(|e| e.kind == ActivityKind::Claim && e.authority == Some(wallet))
+        .map(|e| ClaimRow::from_activity(&e))
+        .collect();
+
+    tracing::event!(Level::INFO, "Claim export successful - Step 2/2");
+
+    // Oldest first reads more naturally in a ledger
+    rows.reverse();
+
+    let next_before = if complete {
+        None
+    } else {
+        signatures[..signatures_scanned]
+            .last()
+            .map(|s| s.signature.clone())
+    };
+
+    let csv = match payload.format.unwrap_or(ExportFormat::Json) {
+        ExportFormat::Csv => Some(ClaimRow::to_csv(&rows)),
+        ExportFormat::Json => None,
+    };
+
+    Ok(Json(ExportClaimsResponse {
+        network_config: payload.network_config,
+        wallet,
+        signatures_scanned: signatures_scanned as u64,
+        complete,
+        next_before,
+        rows,
+        csv,
+    }))
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ the wallet (miner authority)
+///     },
+///     "start_ts": 1609459200, ~ optional, inclusive unix timestamp
+///     "end_ts": 1640995199, ~ optional, inclusive unix timestamp
+///     "format": "Csv", ~ optional, "Json" (default) or "Csv"
+///     "max_signatures": 1000, ~ optional, signatures scanned by this request, at most 1000
+///     "before": "5h6x..." ~ optional, next_before of the previous response to continue from
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct ExportClaimsRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub start_ts: Option<i64>,
+    pub end_ts: Option<i64>,
+    pub format: Option<ExportFormat>,
+    pub max_signatures: Option<usize>,
+    pub before: Option<String>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "wallet": [...],
+///     "signatures_scanned": 1523,
+///     "complete": true, ~ false when max_signatures was hit before reaching start_ts
+///     "next_before": null, ~ set when incomplete, pass as "before" to scan further back
+///     "rows": [...], ~ see ClaimRow
+///     "csv": "timestamp,date,signature,quarry,rewards_mint,gross_amount,claim_fee,net_amount,amount_unknown\n..."
+/// }
+///
+/// csv is only set when the CSV format was requested. Amounts are raw token units.
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct ExportClaimsResponse {
+    pub network_config: NetworkConfig,
+    pub wallet: Pubkey,
+    pub signatures_scanned: u64,
+    pub complete: bool,
+    pub next_before: Option<String>,
+    pub rows: Vec<ClaimRow>,
+    pub csv: Option<String>,
+}
+
+/// A single reward claim
+#[derive(Serialize, Deserialize)]
+pub struct ClaimRow {
+    pub timestamp: Option<i64>,
+    pub signature: String,
+    pub quarry: Option<Pubkey>,
+    pub rewards_mint: Option<Pubkey>,
+    /// Net amount plus the claim fee
+    pub gross_amount: u64,
+    pub claim_fee: u64,
+    /// Amount received by the wallet
+    pub net_amount: u64,
+    /// Set when no ClaimEvent matched the claim to take the amounts from. The amounts are
+    /// then zero and left blank in the CSV.
+    pub amount_unknown: bool,
+}
+
+impl ClaimRow {
+    pub fn from_activity(entry: &ActivityEntry) -> Self {
+        let net_amount = entry.amount.unwrap_or(0);
+        let claim_fee = entry.fees.unwrap_or(0);
+
+        Self {
+            timestamp: entry.block_time,
+            signature: entry.signature.clone(),
+            quarry: entry.quarry,
+            rewards_mint: entry.rewards_token,
+            gross_amount: net_amount.saturating_add(claim_fee),
+            claim_fee,
+            net_amount,
+            amount_unknown: entry.amount.is_none(),
+        }
+    }
+
+    /// Renders the rows as CSV with a header row. Pubkeys are written as base58 strings.
+    pub fn to_csv(rows: &[Self]) -> String {
+        let mut csv = String::from(
+            "timestamp,date,signature,quarry,rewards_mint,gross_amount,claim_fee,net_amount,amount_unknown\n",
+        );
+
+        let key = |k: &Option<Pubkey>| k.map(|k| k.to_string()).unwrap_or_default();
+        let amount = |row: &Self, a: u64| {
+            if row.amount_unknown {
+                String::new()
+            } else {
+                a.to_string()
+            }
+        };
+
+        for row in rows.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{}\n",
+                row.timestamp.map(|t| t.to_string()).unwrap_or_default(),
+                row.timestamp.map(format_unix_timestamp).unwrap_or_default(),
+                row.signature,
+                key(&row.quarry),
+                key(&row.rewards_mint),
+                amount(row, row.gross_amount),
+                amount(row, row.claim_fee),
+                amount(row, row.net_amount),
+                row.amount_unknown
+            ));
+        }
+
+        csv
+    }
+}
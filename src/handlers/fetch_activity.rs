@@ -0,0 +1,189 @@
+use crate::utils::*;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use std::str::FromStr;
+use tracing::Level;
+
+/// Number of signatures scanned when the request doesn't specify a limit
+pub const DEFAULT_ACTIVITY_LIMIT: usize = 25;
+
+/// Most signatures getSignaturesForAddress returns per request
+pub const MAX_ACTIVITY_LIMIT: usize = 1000;
+
+/// Page through the transactions of a miner, quarry or wallet and return the stakes,
+/// withdrawals and claims found in them
+pub async fn fetch_activity_handler(
+    Json(payload): Json<FetchActivityRequest>,
+) -> Result<Json<FetchActivityResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    let parse_cursor = |cursor: &Option<String>| match cursor {
+        Some(value) => Signature::from_str(value).map(Some),
+        None => Ok(None),
+    };
+
+    let (before, until) = match (parse_cursor(&payload.before), parse_cursor(&payload.until)) {
+        (Ok(before), Ok(until)) => (before, until),
+        _ => {
+            tracing::event!(Level::ERROR, "Signature cursor parse failed");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "before and until must be base58 signatures".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_ACTIVITY_LIMIT)
+        .clamp(1, MAX_ACTIVITY_LIMIT);
+
+    let signatures =
+        match fetch_signatures_for_address(&network, &pubkey, before, until, Some(limit)) {
+            Ok(value) => {
+                tracing::event!(Level::INFO, "Signatures fetch successful - Step 1/2");
+                value
+            }
+
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Signatures fetch failed - Step 1/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch signatures".to_string())),
+                ));
+            }
+        };
+
+    let order = payload.order.unwrap_or(ActivityOrder::NewestFirst);
+    let mut transactions: Vec<Vec<ActivityEntry>> = Vec::new();
+
+    for status in signatures.iter().filter(|s| s.err.is_none()) {
+        let signature = match Signature::from_str(&status.signature) {
+            Ok(value) => value,
+            Err(_e) => continue,
+        };
+
+        let decoded = match fetch_transaction(&network, &signature) {
+            Ok(value) => DecodedTransaction::decode(&signature, &value),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Transaction fetch failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch transaction".to_string())),
+                ));
+            }
+        };
+
+        if let Some(tx) = decoded {
+            transactions.push(
+                ActivityEntry::from_transaction(&tx)
+                    .into_iter()
+                    .filter(|entry| entry.involves(&pubkey))
+                    .collect(),
+            );
+        }
+    }
+
+    // Signatures come back newest first. Only whole transactions are reordered, entries of
+    // the same transaction stay in instruction order.
+    if order == ActivityOrder::OldestFirst {
+        transactions.reverse();
+    }
+
+    let activity: Vec<ActivityEntry> = transactions.into_iter().flatten().collect();
+
+    tracing::event!(Level::INFO, "Activity decoding successful - Step 2/2");
+
+    Ok(Json(FetchActivityResponse {
+        network_config: payload.network_config,
+        address: pubkey,
+        order,
+        signatures_scanned: signatures.len() as u64,
+        // A short page means there is nothing older left to scan
+        next_before: if signatures.len() < limit {
+            None
+        } else {
+            signatures.last().map(|s| s.signature.clone())
+        },
+        activity,
+    }))
+}
+
+/// Order of the transactions within a page of activity
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActivityOrder {
+    NewestFirst,
+    OldestFirst,
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ a miner, quarry or wallet
+///     },
+///     "before": "5j7s...", ~ optional, only scan signatures older than this one
+///     "until": "3kZq...", ~ optional, stop scanning at this signature
+///     "limit": 25, ~ optional, number of signatures to scan, at most 1000
+///     "order": "NewestFirst" ~ optional, "NewestFirst" (default) or "OldestFirst"
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchActivityRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub before: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+    pub order: Option<ActivityOrder>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "address": [...],
+///     "order": "NewestFirst", ~ order of the transactions in activity
+///     "signatures_scanned": 25,
+///     "next_before": "2Jx9...", ~ pass as "before" to fetch the next page, null when done
+///     "activity": [
+///         {
+///             "signature": "5j7s...",
+///             "slot": 108000000,
+///             "block_time": 1637000000,
+///             "instruction_index": "1",
+///             "kind": "Stake",
+///             "authority": [...],
+///             "quarry": [...],
+///             "miner": [...],
+///             "amount": 1000000,
+///             "fees": null,
+///             "rewards_token": null
+///         },
+///         ...
+///     ]
+/// }
+///
+/// Transactions are ordered as the response's order says, entries of the same transaction
+/// are always in instruction order. Pages themselves always go back in time.
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchActivityResponse {
+    pub network_config: NetworkConfig,
+    pub address: Pubkey,
+    pub order: ActivityOrder,
+    pub signatures_scanned: u64,
+    pub next_before: Option<String>,
+    pub activity: Vec<ActivityEntry>,
+}
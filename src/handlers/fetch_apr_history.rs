@@ -0,0 +1,223 @@
+use crate::utils::*;
+use crate::{sampled_snapshots, series_range, FetchTimeseriesRequest};
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Trailing windows averaged for every APR point, in seconds
+pub const APR_7D_WINDOW: i64 = 7 * 86_400;
+pub const APR_30D_WINDOW: i64 = 30 * 86_400;
+
+/// Compute a quarry's APR over time from its indexed snapshots and the price history file,
+/// along with 7 and 30 day trailing averages
+pub async fn fetch_apr_history_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Extension(price_source): Extension<Option<Arc<PriceSource>>>,
+    Json(payload): Json<FetchTimeseriesRequest>,
+) -> Result<Json<FetchAprHistoryResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+    let (start_ts, end_ts) = series_range(&payload);
+
+    let prices = match price_source.as_ref().map(|source| source.load()) {
+        Some(Ok(value)) => value,
+        Some(Err(_e)) => {
+            tracing::event!(Level::ERROR, "Price history load failed");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to read price history".to_string())),
+            ));
+        }
+        None => {
+            tracing::event!(Level::ERROR, "Price history disabled");
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(Value::String("PRICE_HISTORY_PATH is not set".to_string())),
+            ));
+        }
+    };
+
+    // Load an extra 30 days so the first points in range have full trailing windows
+    let (index, snapshots) = sampled_snapshots(
+        &index,
+        network,
+        (start_ts - APR_30D_WINDOW, end_ts),
+        payload.resolution,
+    )?;
+
+    let mut quarries = Vec::with_capacity(snapshots.len());
+
+    for snapshot in snapshots.iter() {
+        match index.quarry(snapshot, &pubkey) {
+            Ok(Some(value)) => quarries.push((snapshot, value)),
+            Ok(None) => {}
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Indexed quarry fetch failed - Step 2/4");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to read index".to_string())),
+                ));
+            }
+        }
+    }
+
+    tracing::event!(Level::INFO, "Indexed quarry fetch successful - Step 2/4");
+
+    // The rewards mint never changes, so any snapshot of the rewarder will do
+    let rewards_mint = match quarries.last().and_then(|(snapshot, quarry)| {
+        index
+            .rewarder(snapshot, &quarry.rewarder_key)
+            .ok()
+            .flatten()
+            .map(|rewarder| rewarder.rewards_token_mint)
+    }) {
+        Some(value) => value,
+        None => {
+            tracing::event!(Level::ERROR, "Quarry not indexed - Step 3/4");
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(Value::String("Quarry not found in the index".to_string())),
+            ));
+        }
+    };
+
+    let rewards_decimals = match fetch_account(&network, &rewards_mint)
+        .ok()
+        .and_then(|account| spl_token::state::Mint::unpack(&account.data).ok())
+    {
+        Some(mint) => {
+            tracing::event!(Level::INFO, "Rewards mint fetch successful - Step 3/4");
+            mint.decimals
+        }
+
+        None => {
+            tracing::event!(Level::ERROR, "Rewards mint fetch failed - Step 3/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch rewards mint".to_string())),
+            ));
+        }
+    };
+
+    let mut points: Vec<AprPoint> = quarries
+        .iter()
+        .map(|(snapshot, quarry)| {
+            let ts = snapshot.timestamp;
+            let rate = effective_rewards_rate(quarry, ts);
+            let staked_price = prices.price_at(&quarry.token_mint_key, ts);
+            let rewards_price = prices.price_at(&rewards_mint, ts);
+
+            AprPoint {
+                timestamp: ts,
+                slot: snapshot.slot,
+                total_tokens_deposited: quarry.total_tokens_deposited,
+                effective_rewards_rate: rate,
+                staked_price,
+                rewards_price,
+                apr: match (staked_price, rewards_price) {
+                    (Some(s), Some(r)) => quarry_apr(quarry, rate, s, r, rewards_decimals),
+                    _ => None,
+                },
+                apr_7d: None,
+                apr_30d: None,
+            }
+        })
+        .collect();
+
+    let averages: Vec<(Option<f64>, Option<f64>)> = (1..=points.len())
+        .map(|end| {
+            (
+                trailing_average(&points[..end], APR_7D_WINDOW),
+                trailing_average(&points[..end], APR_30D_WINDOW),
+            )
+        })
+        .collect();
+
+    for (point, (apr_7d, apr_30d)) in points.iter_mut().zip(averages) {
+        point.apr_7d = apr_7d;
+        point.apr_30d = apr_30d;
+    }
+
+    points.retain(|p| p.timestamp >= start_ts);
+
+    tracing::event!(Level::INFO, "APR series build successful - Step 4/4");
+
+    Ok(Json(FetchAprHistoryResponse {
+        network_config: payload.network_config,
+        quarry_key: pubkey,
+        rewards_mint,
+        resolution: payload.resolution,
+        apr_7d: points.last().and_then(|p| p.apr_7d),
+        apr_30d: points.last().and_then(|p| p.apr_30d),
+        points,
+    }))
+}
+
+/// Mean APR of the points within `window` seconds of the last one, skipping points without
+/// prices
+fn trailing_average(points: &[AprPoint], window: i64) -> Option<f64> {
+    let end = points.last()?.timestamp;
+    let aprs: Vec<f64> = points
+        .iter()
+        .filter(|p| p.timestamp > end - window)
+        .filter_map(|p| p.apr)
+        .collect();
+
+    if aprs.is_empty() {
+        None
+    } else {
+        Some(aprs.iter().sum::<f64>() / aprs.len() as f64)
+    }
+}
+
+// Fetch APR history request is simply a FetchTimeseriesRequest object, with the pubkey being
+// the quarry
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry_key": [...],
+///     "rewards_mint": [...],
+///     "resolution": "Daily",
+///     "apr_7d": 0.184, ~ trailing averages as of the last point
+///     "apr_30d": 0.212,
+///     "points": [...] ~ oldest first, see AprPoint
+/// }
+///
+/// APRs are fractions, 0.25 is 25%, and are gross of claim fees.
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchAprHistoryResponse {
+    pub network_config: NetworkConfig,
+    pub quarry_key: Pubkey,
+    pub rewards_mint: Pubkey,
+    pub resolution: SeriesResolution,
+    pub apr_7d: Option<f64>,
+    pub apr_30d: Option<f64>,
+    pub points: Vec<AprPoint>,
+}
+
+/// APR of a quarry at the last indexed run of a bucket
+#[derive(Serialize, Deserialize)]
+pub struct AprPoint {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub total_tokens_deposited: u64,
+    pub effective_rewards_rate: u64,
+    /// USD price of one whole staked token, None when the price history has no recent price
+    pub staked_price: Option<f64>,
+    /// USD price of one whole rewards token
+    pub rewards_price: Option<f64>,
+    pub apr: Option<f64>,
+    pub apr_7d: Option<f64>,
+    pub apr_30d: Option<f64>,
+}
@@ -0,0 +1,133 @@
+use crate::utils::*;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentLevel;
+use tracing::Level;
+
+/// Fetch every quarry_mine account of the requested type whose fields equal the values given,
+/// filtered by the RPC node with Memcmp filters built from the account layout
+pub async fn fetch_filtered_accounts_handler(
+    Json(payload): Json<FetchFilteredAccountsRequest>,
+) -> Result<Json<FetchFilteredAccountsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let account_type = payload.account_type;
+
+    let filters = match AccountFilters::from_request(account_type, &payload.filters) {
+        Ok(value) => value,
+        Err(message) => {
+            tracing::event!(Level::ERROR, "Filter build failed - Step 1/3");
+            return Err((StatusCode::BAD_REQUEST, Json(Value::String(message))));
+        }
+    };
+
+    // An unfiltered miner scan returns every miner of every quarry
+    if account_type == AccountType::Miner && filters.field_filter_count() == 0 {
+        tracing::event!(Level::ERROR, "Unfiltered miner scan requested - Step 1/3");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String(
+                "Miner scans need at least one field filter".to_string(),
+            )),
+        ));
+    }
+
+    let accounts = match fetch_program_accounts(
+        &network,
+        &quarry_mine::ID,
+        Some(filters.build()),
+        Some(CommitmentLevel::Confirmed),
+    ) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Filtered accounts fetch successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Filtered accounts fetch failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    let mut entries = Vec::with_capacity(accounts.len());
+
+    for (pubkey, account) in accounts.iter() {
+        match QuarryAccount::decode(account) {
+            Some(value) => entries.push(FilteredAccountEntry {
+                pubkey: *pubkey,
+                account: value.to_value(),
+            }),
+            None => {
+                tracing::event!(Level::ERROR, "Account decode failed - Step 3/3");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to decode account".to_string())),
+                ));
+            }
+        }
+    }
+
+    tracing::event!(Level::INFO, "Account decode successful - Step 3/3");
+
+    Ok(Json(FetchFilteredAccountsResponse {
+        network_config: payload.network_config,
+        account_type,
+        accounts: entries,
+    }))
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "account_type": "Miner", ~ "Quarry", "Miner" or "Rewarder"
+///     "filters": [ ~ every filter must match, miner scans need at least one
+///         {
+///             "field": "authority",
+///             "value": [...]
+///         }
+///     ]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchFilteredAccountsRequest {
+    pub network_config: NetworkConfig,
+    pub account_type: AccountType,
+    pub filters: Vec<FieldFilter>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "account_type": "Miner",
+///     "accounts": [
+///         {
+///             "pubkey": [...],
+///             "account": {...} ~ JSON representation of the account (see MinerWrapper etc.)
+///         },
+///         ...
+///     ]
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchFilteredAccountsResponse {
+    pub network_config: NetworkConfig,
+    pub account_type: AccountType,
+    pub accounts: Vec<FilteredAccountEntry>,
+}
+
+/// A decoded account along with the address it lives at
+#[derive(Serialize, Deserialize)]
+pub struct FilteredAccountEntry {
+    pub pubkey: Pubkey,
+    pub account: Value,
+}
@@ -1,24 +1,43 @@
 use crate::utils::*;
 use anchor_client::anchor_lang::AccountDeserialize;
+use axum::extract::Extension;
 use axum::{http::StatusCode, Json};
 use quarry_mine::Miner;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{account::Account, program_error::ProgramError};
+use std::sync::Arc;
 use tracing::Level;
 
-/// Fetch the miner account specified and deserialize to JSON
+/// Fetch the miner account specified and deserialize to JSON. The miner can either be passed
+/// in directly or located from its quarry and authority. Served from the account cache while
+/// fresh.
 pub async fn fetch_miner_handler(
-    Json(payload): Json<FetchAccountRequest>,
+    Extension(cache): Extension<Arc<AccountCache>>,
+    Json(payload): Json<FetchMinerRequest>,
 ) -> Result<Json<FetchMinerResponse>, (StatusCode, Json<serde_json::Value>)> {
     let network = payload.network_config.variant;
-    let pubkey = payload.pubkey_config.pubkey;
 
-    let account = fetch_account(&network, &pubkey);
+    let pubkey = match (&payload.pubkey_config, payload.quarry, payload.authority) {
+        (Some(pubkey_config), _, _) => pubkey_config.pubkey,
+        (None, Some(quarry), Some(authority)) => find_miner_address(&quarry, &authority).0,
+        _ => {
+            tracing::event!(Level::ERROR, "Miner request is missing a pubkey");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "Either pubkey_config or quarry and authority must be set".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let commitment = payload.commitment.unwrap_or_default();
+    let account = cache.fetch(network, &pubkey, commitment, CachedAccountKind::Miner);
 
     match account {
-        Ok(value) => {
+        Ok((value, cache_metadata)) => {
             tracing::event!(Level::INFO, "Account fetch successful - Step 1/2");
             let try_wrap = MinerWrapper::wrap(&value);
 
@@ -27,7 +46,9 @@ pub async fn fetch_miner_handler(
                     tracing::event!(Level::INFO, "Miner wrap successful - Step 2/2");
                     Ok(Json(FetchMinerResponse {
                         network_config: payload.network_config,
+                        miner_key: pubkey,
                         miner: value,
+                        cache: cache_metadata,
                     }))
                 }
 
@@ -51,25 +72,61 @@ pub async fn fetch_miner_handler(
     }
 }
 
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ an array of 32 unsigned 8-bit integers
+///     }
+/// }
+///
+/// or, to locate the miner from its quarry and authority
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry": [...],
+///     "authority": [...]
+/// }
+///
+/// Both forms also take an optional "commitment": "Processed", "Confirmed" (default) or
+/// "Finalized"
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinerRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: Option<PubkeyConfig>,
+    pub quarry: Option<Pubkey>,
+    pub authority: Option<Pubkey>,
+    pub commitment: Option<Commitment>,
+}
+
 /// Example response
 ///
 /// {
 ///     "network_config": {
 ///         "variant": "Mainnet"
 ///     },
-///     "miner": {...} ~ JSON representation of Miner (see MinerWrapper for format)
+///     "miner_key": [...],
+///     "miner": {...}, ~ JSON representation of Miner (see MinerWrapper for format)
+///     "cache": {...} ~ see CacheMetadata
 /// }
 ///
 /// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
 #[derive(Serialize, Deserialize)]
 pub struct FetchMinerResponse {
     pub network_config: NetworkConfig,
+    pub miner_key: Pubkey,
     pub miner: MinerWrapper,
+    pub cache: CacheMetadata,
 }
 
 /// This type is required because Miner accounts do not natively implement Serialize and
 /// Deserialize by default which is needed for axum handler response
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MinerWrapper {
     /// Key of the [Quarry] this [Miner] works on.
     pub quarry_key: Pubkey,
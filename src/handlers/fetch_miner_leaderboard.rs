@@ -0,0 +1,225 @@
+use crate::utils::*;
+use crate::{MinerEntry, QuarryWrapper};
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Number of miners returned when the request doesn't specify a limit
+pub const DEFAULT_LEADERBOARD_LIMIT: usize = 10;
+
+/// Fetch every miner of the quarry specified and return the largest positions along with
+/// stake concentration metrics. Served from the index when indexer mode is on.
+pub async fn fetch_miner_leaderboard_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchMinerLeaderboardRequest>,
+) -> Result<Json<FetchMinerLeaderboardResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+    let limit = payload.limit.unwrap_or(DEFAULT_LEADERBOARD_LIMIT);
+    let dust_threshold = payload.dust_threshold.unwrap_or(0);
+
+    // Quarries created since the last index run fall through to RPC
+    if let Some((index, snapshot)) = latest_index_snapshot(&index, network) {
+        if let (Ok(Some(quarry)), Ok(miners)) = (
+            index.quarry(&snapshot, &pubkey),
+            index.miners_for_quarry(network, &pubkey),
+        ) {
+            tracing::event!(
+                Level::INFO,
+                "Indexed leaderboard fetch successful - Step 1/1"
+            );
+            let miners = MinerEntry::from_indexed(miners);
+
+            return Ok(Json(FetchMinerLeaderboardResponse {
+                network_config: payload.network_config,
+                quarry_key: pubkey,
+                total_tokens_deposited: quarry.total_tokens_deposited,
+                leaderboard: MinerLeaderboard::compute(&quarry, miners, limit, dust_threshold),
+                index_status: Some(IndexStatus::new(&snapshot, index.chain_slot(network))),
+            }));
+        }
+    }
+
+    let quarry = match fetch_account(&network, &pubkey) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Account fetch successful - Step 1/3");
+            match QuarryWrapper::wrap(&value) {
+                Ok(value) => value,
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Quarry wrap failed - Step 1/3");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to wrap Quarry".to_string())),
+                    ));
+                }
+            }
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch account".to_string())),
+            ));
+        }
+    };
+
+    let miner_accounts = match fetch_miners_for_quarry(&network, &pubkey) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Miners fetch successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Miners fetch failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch miners".to_string())),
+            ));
+        }
+    };
+
+    let miners = match MinerEntry::wrap_all(&miner_accounts) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Miner wrap successful - Step 3/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Miner wrap failed - Step 3/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to wrap Miner".to_string())),
+            ));
+        }
+    };
+
+    let leaderboard = MinerLeaderboard::compute(&quarry, miners, limit, dust_threshold);
+
+    Ok(Json(FetchMinerLeaderboardResponse {
+        network_config: payload.network_config,
+        quarry_key: pubkey,
+        total_tokens_deposited: quarry.total_tokens_deposited,
+        leaderboard,
+        index_status: None,
+    }))
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ the quarry to rank miners for
+///     },
+///     "limit": 25, ~ optional, defaults to 10
+///     "dust_threshold": 1000 ~ optional, balances at or below this are counted as dust
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinerLeaderboardRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub limit: Option<usize>,
+    pub dust_threshold: Option<u64>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry_key": [...],
+///     "total_tokens_deposited": 1000000000,
+///     "leaderboard": {...}, ~ Top miners and distribution metrics (see MinerLeaderboard)
+///     "index_status": {...} ~ null when served live from RPC (see IndexStatus)
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinerLeaderboardResponse {
+    pub network_config: NetworkConfig,
+    pub quarry_key: Pubkey,
+    pub total_tokens_deposited: u64,
+    pub leaderboard: MinerLeaderboard,
+    pub index_status: Option<IndexStatus>,
+}
+
+/// Largest miners of a quarry plus metrics describing how concentrated the stake is
+#[derive(Serialize, Deserialize)]
+pub struct MinerLeaderboard {
+    pub top_miners: Vec<RankedMiner>,
+    pub miners_found: u64,
+    /// Gini coefficient of miner balances, 0 is perfectly equal and 1 is a single whale
+    pub gini_coefficient: f64,
+    /// Share of total_tokens_deposited held by the 10 largest miners, between 0 and 1
+    pub top_10_share: f64,
+    /// Miners with a balance of exactly zero
+    pub zero_balance_miners: u64,
+    /// Miners with a non zero balance at or below the dust threshold
+    pub dust_miners: u64,
+    /// Position sizes bucketed by order of magnitude
+    pub histogram: Vec<HistogramBucket>,
+}
+
+/// A miner along with its rank and share of the quarry
+#[derive(Serialize, Deserialize)]
+pub struct RankedMiner {
+    /// 1 based rank by balance
+    pub rank: u64,
+    /// balance / total_tokens_deposited, between 0 and 1
+    pub share: f64,
+    #[serde(flatten)]
+    pub entry: MinerEntry,
+}
+
+impl MinerLeaderboard {
+    pub fn compute(
+        quarry: &QuarryWrapper,
+        mut miners: Vec<MinerEntry>,
+        limit: usize,
+        dust_threshold: u64,
+    ) -> Self {
+        miners.sort_by(|a, b| b.miner.balance.cmp(&a.miner.balance));
+
+        let total = quarry.total_tokens_deposited;
+        let share_of = |balance: u64| -> f64 {
+            if total == 0 {
+                0.0
+            } else {
+                balance as f64 / total as f64
+            }
+        };
+
+        let balances: Vec<u64> = miners.iter().map(|m| m.miner.balance).collect();
+        let top_10_total: u64 = balances.iter().take(10).sum();
+
+        Self {
+            miners_found: miners.len() as u64,
+            gini_coefficient: gini_coefficient(&balances),
+            top_10_share: share_of(top_10_total),
+            zero_balance_miners: balances.iter().filter(|b| **b == 0).count() as u64,
+            dust_miners: balances
+                .iter()
+                .filter(|b| **b > 0 && **b <= dust_threshold)
+                .count() as u64,
+            histogram: log10_histogram(&balances),
+            top_miners: miners
+                .into_iter()
+                .take(limit)
+                .enumerate()
+                .map(|(i, entry)| RankedMiner {
+                    rank: i as u64 + 1,
+                    share: share_of(entry.miner.balance),
+                    entry,
+                })
+                .collect(),
+        }
+    }
+}
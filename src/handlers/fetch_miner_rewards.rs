@@ -0,0 +1,195 @@
+use crate::utils::*;
+use crate::{FetchMinerRequest, MinerWrapper, QuarryWrapper, RewarderWrapper};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use tracing::Level;
+
+/// Compute the rewards the miner specified could claim, from its Miner, Quarry and Rewarder
+/// read together at a single slot. The miner can either be passed in directly or located from
+/// its quarry and authority.
+pub async fn fetch_miner_rewards_handler(
+    Json(payload): Json<FetchMinerRequest>,
+) -> Result<Json<FetchMinerRewardsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let commitment = payload.commitment.unwrap_or_default();
+
+    let (miner_key, known_quarry) =
+        match (&payload.pubkey_config, payload.quarry, payload.authority) {
+            (Some(pubkey_config), _, _) => (pubkey_config.pubkey, None),
+            (None, Some(quarry), Some(authority)) => {
+                (find_miner_address(&quarry, &authority).0, Some(quarry))
+            }
+            _ => {
+                tracing::event!(Level::ERROR, "Miner rewards request is missing a pubkey");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(Value::String(
+                        "Either pubkey_config or quarry and authority must be set".to_string(),
+                    )),
+                ));
+            }
+        };
+
+    // Account links never change once created, so the keys can be discovered from reads at any
+    // slot before the consistent read
+    let quarry_key = match known_quarry {
+        Some(quarry_key) => quarry_key,
+        None => match fetch_account(&network, &miner_key)
+            .ok()
+            .and_then(|account| MinerWrapper::wrap(&account).ok())
+        {
+            Some(miner) => miner.quarry_key,
+            None => {
+                tracing::event!(Level::ERROR, "Miner lookup failed - Step 1/3");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch miner".to_string())),
+                ));
+            }
+        },
+    };
+
+    let rewarder_key = match fetch_account(&network, &quarry_key)
+        .ok()
+        .and_then(|account| QuarryWrapper::wrap(&account).ok())
+    {
+        Some(quarry) => {
+            tracing::event!(Level::INFO, "Account lookup successful - Step 1/3");
+            quarry.rewarder_key
+        }
+        None => {
+            tracing::event!(Level::ERROR, "Quarry lookup failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch quarry".to_string())),
+            ));
+        }
+    };
+
+    let read = match fetch_consistent_accounts(
+        &network,
+        &[miner_key, quarry_key, rewarder_key],
+        commitment,
+    ) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Consistent read successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Consistent read failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    let wrapped = match &read.accounts[..] {
+        [Some(miner), Some(quarry), Some(rewarder)] => (
+            MinerWrapper::wrap(miner),
+            QuarryWrapper::wrap(quarry),
+            RewarderWrapper::wrap(rewarder),
+        ),
+        _ => {
+            tracing::event!(Level::ERROR, "Account missing at read slot - Step 3/3");
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(Value::String(
+                    "Miner, quarry or rewarder does not exist".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let (miner, quarry, rewarder) = match wrapped {
+        (Ok(miner), Ok(quarry), Ok(rewarder)) => {
+            tracing::event!(Level::INFO, "Account wrap successful - Step 3/3");
+            (miner, quarry, rewarder)
+        }
+
+        _ => {
+            tracing::event!(Level::ERROR, "Account wrap failed - Step 3/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to wrap accounts".to_string())),
+            ));
+        }
+    };
+
+    let rewards = MinerRewards::compute(&miner, &quarry, read.context.unix_timestamp);
+
+    Ok(Json(FetchMinerRewardsResponse {
+        network_config: payload.network_config,
+        miner_key,
+        quarry_key,
+        rewarder_key,
+        rewards_token_mint: rewarder.rewards_token_mint,
+        computed_at: read.context,
+        rewards,
+        miner,
+        quarry,
+    }))
+}
+
+// Fetch miner rewards request is simply a FetchMinerRequest object
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "miner_key": [...],
+///     "quarry_key": [...],
+///     "rewarder_key": [...],
+///     "rewards_token_mint": [...],
+///     "computed_at": {...}, ~ slot and Clock timestamp everything was read at (see ReadContext)
+///     "rewards": {...}, ~ see MinerRewards
+///     "miner": {...}, ~ JSON representation of Miner at computed_at.slot
+///     "quarry": {...} ~ JSON representation of Quarry at computed_at.slot
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinerRewardsResponse {
+    pub network_config: NetworkConfig,
+    pub miner_key: Pubkey,
+    pub quarry_key: Pubkey,
+    pub rewarder_key: Pubkey,
+    pub rewards_token_mint: Pubkey,
+    pub computed_at: ReadContext,
+    pub rewards: MinerRewards,
+    pub miner: MinerWrapper,
+    pub quarry: QuarryWrapper,
+}
+
+/// Rewards owed to a miner, in the rewarder's rewards token
+#[derive(Serialize, Deserialize)]
+pub struct MinerRewards {
+    /// Rewards the miner could claim at computed_at, before claim fees
+    pub pending_rewards: u128,
+    /// balance / total_tokens_deposited, between 0 and 1
+    pub share_of_quarry: f64,
+    /// Rewards the miner would earn over a year at the quarry's current effective rate
+    pub projected_annual_rewards: u64,
+}
+
+impl MinerRewards {
+    pub fn compute(miner: &MinerWrapper, quarry: &QuarryWrapper, now: i64) -> Self {
+        let share_of_quarry = if quarry.total_tokens_deposited == 0 {
+            0.0
+        } else {
+            miner.balance as f64 / quarry.total_tokens_deposited as f64
+        };
+
+        Self {
+            pending_rewards: calculate_rewards_earned(quarry, miner, now),
+            share_of_quarry,
+            projected_annual_rewards: (effective_rewards_rate(quarry, now) as f64 * share_of_quarry)
+                as u64,
+        }
+    }
+}
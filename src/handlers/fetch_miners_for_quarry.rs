@@ -1,10 +1,254 @@
 use crate::utils::*;
 use crate::MinerWrapper;
-use anchor_client::anchor_lang::AccountDeserialize;
+use axum::extract::Extension;
 use axum::{http::StatusCode, Json};
-use quarry_mine::{Miner, Quarry};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{account::Account, program_error::ProgramError};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use tracing::Level;
+
+/// Fetch every miner working on the quarry specified and deserialize them to JSON. Served
+/// from the index when indexer mode is on, unless only some fields were requested.
+pub async fn fetch_miners_for_quarry_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchMinersForQuarryRequest>,
+) -> Result<Json<FetchMinersForQuarryResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    if let Some(fields) = &payload.fields {
+        let projection = match Projection::new(AccountType::Miner, fields) {
+            Ok(value) => value,
+            Err(name) => {
+                tracing::event!(Level::ERROR, "Unknown Miner field requested");
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    Json(Value::String(format!("Unknown Miner field: {}", name))),
+                ));
+            }
+        };
+
+        return match fetch_miners_for_quarry_sliced(
+            &network,
+            &pubkey,
+            Some(projection.data_slice()),
+        ) {
+            Ok(value) => match MinerView::decode_all(&projection, &value) {
+                Some(views) => {
+                    tracing::event!(Level::INFO, "Projected miners fetch successful - Step 1/1");
+                    Ok(Json(FetchMinersForQuarryResponse {
+                        network_config: payload.network_config,
+                        quarry_key: pubkey,
+                        miners: MinerList::Projected(views),
+                        index_status: None,
+                    }))
+                }
+
+                None => {
+                    tracing::event!(Level::ERROR, "Miner view decode failed - Step 1/1");
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to decode Miner fields".to_string())),
+                    ))
+                }
+            },
+
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Projected miners fetch failed - Step 1/1");
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch miners".to_string())),
+                ))
+            }
+        };
+    }
+
+    if let Some((index, snapshot)) = latest_index_snapshot(&index, network) {
+        // Quarries created since the last index run fall through to RPC
+        let indexed = match index.quarry(&snapshot, &pubkey) {
+            Ok(value) => value.is_some(),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Indexed quarry fetch failed - Step 1/1");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to read index".to_string())),
+                ));
+            }
+        };
+
+        if indexed {
+            return match index.miners_for_quarry(network, &pubkey) {
+                Ok(value) => {
+                    tracing::event!(Level::INFO, "Indexed miners fetch successful - Step 1/1");
+                    Ok(Json(FetchMinersForQuarryResponse {
+                        network_config: payload.network_config,
+                        quarry_key: pubkey,
+                        miners: MinerList::Full(MinerEntry::from_indexed(value)),
+                        index_status: Some(IndexStatus::new(&snapshot, index.chain_slot(network))),
+                    }))
+                }
+
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Indexed miners fetch failed - Step 1/1");
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to read index".to_string())),
+                    ))
+                }
+            };
+        }
+    }
+
+    let accounts = fetch_miners_for_quarry(&network, &pubkey);
+
+    match accounts {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Miners fetch successful - Step 1/2");
+            let try_wrap = MinerEntry::wrap_all(&value);
+
+            match try_wrap {
+                Ok(value) => {
+                    tracing::event!(Level::INFO, "Miner wrap successful - Step 2/2");
+                    Ok(Json(FetchMinersForQuarryResponse {
+                        network_config: payload.network_config,
+                        quarry_key: pubkey,
+                        miners: MinerList::Full(value),
+                        index_status: None,
+                    }))
+                }
+
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Miner wrap failed - Step 2/2");
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to wrap Miner".to_string())),
+                    ))
+                }
+            }
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Miners fetch failed - Step 1/2");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch miners".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ the quarry to list miners for
+///     },
+///     "fields": ["authority", "balance"] ~ optional, only these Miner fields are fetched
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinersForQuarryRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub fields: Option<Vec<String>>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry_key": [...],
+///     "miners": [
+///         {
+///             "miner_key": [...],
+///             "miner": {...} ~ JSON representation of Miner, or only the requested fields
+///         },
+///         ...
+///     ],
+///     "index_status": { ~ null when served live from RPC
+///         "slot": 108000000, ~ slot of the indexed snapshot
+///         "indexed_at": 1637000000,
+///         "slot_lag": 750, ~ slots the index is behind the chain
+///         "seconds_behind": 300
+///     }
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchMinersForQuarryResponse {
+    pub network_config: NetworkConfig,
+    pub quarry_key: Pubkey,
+    pub miners: MinerList,
+    pub index_status: Option<IndexStatus>,
+}
+
+/// Miners of a quarry, either fully wrapped or as partial views of the requested fields
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MinerList {
+    Full(Vec<MinerEntry>),
+    Projected(Vec<MinerView>),
+}
+
+/// The projected fields of a miner along with the address it lives at
+#[derive(Serialize, Deserialize)]
+pub struct MinerView {
+    pub miner_key: Pubkey,
+    pub miner: BTreeMap<String, FieldValue>,
+}
+
+impl MinerView {
+    /// Decodes every sliced (pubkey, account) tuple returned for the projection
+    pub fn decode_all(
+        projection: &Projection,
+        accounts: &[(Pubkey, Account)],
+    ) -> Option<Vec<Self>> {
+        accounts
+            .iter()
+            .map(|(miner_key, account)| {
+                projection.decode(&account.data).map(|miner| Self {
+                    miner_key: *miner_key,
+                    miner,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A wrapped miner along with the address it lives at
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MinerEntry {
+    pub miner_key: Pubkey,
+    pub miner: MinerWrapper,
+}
+
+impl MinerEntry {
+    /// Wraps every (pubkey, account) tuple returned from a program account scan, failing if
+    /// any of them is not a valid Miner
+    pub fn wrap_all(accounts: &[(Pubkey, Account)]) -> Result<Vec<Self>, ProgramError> {
+        let mut entries = Vec::with_capacity(accounts.len());
+
+        for (miner_key, account) in accounts.iter() {
+            entries.push(Self {
+                miner_key: *miner_key,
+                miner: MinerWrapper::wrap(account)?,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    pub fn from_indexed(miners: Vec<(Pubkey, MinerWrapper)>) -> Vec<Self> {
+        miners
+            .into_iter()
+            .map(|(miner_key, miner)| Self { miner_key, miner })
+            .collect()
+    }
+}
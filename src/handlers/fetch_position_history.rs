@@ -0,0 +1,157 @@
+use crate::utils::*;
+use crate::{sampled_snapshots, series_range, FetchTimeseriesRequest, MinerWrapper};
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Return a wallet's staked balance and accrued rewards over time for every quarry it mines,
+/// rebuilt from the indexed miner history
+pub async fn fetch_position_history_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchTimeseriesRequest>,
+) -> Result<Json<FetchPositionHistoryResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let wallet = payload.pubkey_config.pubkey;
+
+    let (index, snapshots) =
+        sampled_snapshots(&index, network, series_range(&payload), payload.resolution)?;
+
+    let history = match index.miner_history_for_authority(network, &wallet) {
+        Ok(value) => value,
+        Err(_e) => {
+            tracing::event!(
+                Level::ERROR,
+                "Indexed miner history fetch failed - Step 2/2"
+            );
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to read index".to_string())),
+            ));
+        }
+    };
+
+    // Versions of each miner, oldest first
+    let mut miners: BTreeMap<Pubkey, Vec<(i64, MinerWrapper)>> = BTreeMap::new();
+    for (miner_key, snapshot_id, miner) in history.into_iter() {
+        miners
+            .entry(miner_key)
+            .or_default()
+            .push((snapshot_id, miner));
+    }
+
+    let mut positions = Vec::with_capacity(miners.len());
+
+    for (miner_key, versions) in miners.iter() {
+        let quarry_key = versions[0].1.quarry_key;
+        let mut token_mint_key = None;
+        let mut points = Vec::new();
+
+        for snapshot in snapshots.iter() {
+            // The miner as of this run is its latest version recorded at or before it
+            let miner = match versions.iter().rev().find(|(id, _)| *id <= snapshot.id) {
+                Some((_, value)) => value,
+                None => continue,
+            };
+
+            let quarry = match index.quarry(snapshot, &quarry_key) {
+                Ok(Some(value)) => value,
+                Ok(None) => continue,
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Indexed quarry fetch failed - Step 2/2");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to read index".to_string())),
+                    ));
+                }
+            };
+
+            token_mint_key = Some(quarry.token_mint_key);
+            points.push(PositionPoint {
+                timestamp: snapshot.timestamp,
+                slot: snapshot.slot,
+                balance: miner.balance,
+                rewards_earned: calculate_rewards_earned(&quarry, miner, snapshot.timestamp)
+                    .min(u64::MAX as u128) as u64,
+            });
+        }
+
+        positions.push(PositionSeries {
+            miner_key: *miner_key,
+            quarry_key,
+            token_mint_key,
+            points,
+        });
+    }
+
+    tracing::event!(Level::INFO, "Position series build successful - Step 2/2");
+
+    Ok(Json(FetchPositionHistoryResponse {
+        network_config: payload.network_config,
+        wallet,
+        resolution: payload.resolution,
+        positions,
+    }))
+}
+
+// Fetch position history request is simply a FetchTimeseriesRequest object, with the pubkey
+// being the wallet (miner authority)
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "wallet": [...],
+///     "resolution": "Daily",
+///     "positions": [
+///         {
+///             "miner_key": [...],
+///             "quarry_key": [...],
+///             "token_mint_key": [...], ~ null if the quarry has no snapshot in range
+///             "points": [
+///                 {
+///                     "timestamp": 1637000000,
+///                     "slot": 108000000,
+///                     "balance": 1000000, ~ staked tokens
+///                     "rewards_earned": 52000 ~ claimable rewards at that time
+///                 },
+///                 ...
+///             ]
+///         },
+///         ...
+///     ]
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchPositionHistoryResponse {
+    pub network_config: NetworkConfig,
+    pub wallet: Pubkey,
+    pub resolution: SeriesResolution,
+    pub positions: Vec<PositionSeries>,
+}
+
+/// History of a single miner owned by the wallet
+#[derive(Serialize, Deserialize)]
+pub struct PositionSeries {
+    pub miner_key: Pubkey,
+    pub quarry_key: Pubkey,
+    pub token_mint_key: Option<Pubkey>,
+    pub points: Vec<PositionPoint>,
+}
+
+/// A miner's position at the last indexed run of a bucket
+#[derive(Serialize, Deserialize)]
+pub struct PositionPoint {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub balance: u64,
+    /// Rewards claimable at `timestamp`, including those already checkpointed on the miner
+    pub rewards_earned: u64,
+}
@@ -1,24 +1,29 @@
 use crate::utils::*;
 use anchor_client::anchor_lang::AccountDeserialize;
+use axum::extract::Extension;
 use axum::{http::StatusCode, Json};
 use quarry_mine::Quarry;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{account::Account, program_error::ProgramError};
+use std::sync::Arc;
 use tracing::Level;
 
-/// Fetch the quarry account specified and deserialize to JSON
+/// Fetch the quarry account specified and deserialize to JSON, serving it from the
+/// account cache while fresh
 pub async fn fetch_quarry_handler(
-    Json(payload): Json<FetchAccountRequest>,
+    Extension(cache): Extension<Arc<AccountCache>>,
+    Json(payload): Json<FetchCachedAccountRequest>,
 ) -> Result<Json<FetchQuarryResponse>, (StatusCode, Json<serde_json::Value>)> {
     let network = payload.network_config.variant;
     let pubkey = payload.pubkey_config.pubkey;
+    let commitment = payload.commitment.unwrap_or_default();
 
-    let account = fetch_account(&network, &pubkey);
+    let account = cache.fetch(network, &pubkey, commitment, CachedAccountKind::Quarry);
 
     match account {
-        Ok(value) => {
+        Ok((value, cache_metadata)) => {
             tracing::event!(Level::INFO, "Account fetch successful - Step 1/2");
             let try_wrap = QuarryWrapper::wrap(&value);
 
@@ -28,6 +33,7 @@ pub async fn fetch_quarry_handler(
                     Ok(Json(FetchQuarryResponse {
                         network_config: payload.network_config,
                         quarry: value,
+                        cache: cache_metadata,
                     }))
                 }
 
@@ -57,7 +63,12 @@ pub async fn fetch_quarry_handler(
 ///     "network_config": {
 ///         "variant": "Mainnet"
 ///     },
-///     quarry: {...} ~ JSON representation of Quarry (see QuarryWrapper for format)
+///     quarry: {...}, ~ JSON representation of Quarry (see QuarryWrapper for format)
+///     "cache": {
+///         "hit": true, ~ false when the account was fetched for this request
+///         "slot": 108000000, ~ slot the account data was read at
+///         "age_ms": 1200
+///     }
 /// }
 ///
 /// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
@@ -65,11 +76,12 @@ pub async fn fetch_quarry_handler(
 pub struct FetchQuarryResponse {
     pub network_config: NetworkConfig,
     pub quarry: QuarryWrapper,
+    pub cache: CacheMetadata,
 }
 
 /// This type is required because quarry accounts do not natively implement Serialize and
 /// Deserialize by default which is needed for axum handler response
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct QuarryWrapper {
     /// Rewarder who owns this quarry
     pub rewarder_key: Pubkey,
@@ -100,7 +112,7 @@ pub struct QuarryWrapper {
 }
 
 impl QuarryWrapper {
-    fn wrap(quarry: &Account) -> Result<Self, ProgramError> {
+    pub fn wrap(quarry: &Account) -> Result<Self, ProgramError> {
         let res = deserialize_quarry(quarry);
 
         match res {
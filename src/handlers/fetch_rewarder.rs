@@ -1,24 +1,29 @@
 use crate::utils::*;
 use anchor_client::anchor_lang::AccountDeserialize;
+use axum::extract::Extension;
 use axum::{http::StatusCode, Json};
 use quarry_mine::Rewarder;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::{account::Account, program_error::ProgramError};
+use std::sync::Arc;
 use tracing::Level;
 
-/// Fetch the rewarder account specified and deserialize to JSON
+/// Fetch the rewarder account specified and deserialize to JSON, serving it from the
+/// account cache while fresh
 pub async fn fetch_rewarder_handler(
-    Json(payload): Json<FetchAccountRequest>,
+    Extension(cache): Extension<Arc<AccountCache>>,
+    Json(payload): Json<FetchCachedAccountRequest>,
 ) -> Result<Json<FetchRewarderResponse>, (StatusCode, Json<serde_json::Value>)> {
     let network = payload.network_config.variant;
     let pubkey = payload.pubkey_config.pubkey;
+    let commitment = payload.commitment.unwrap_or_default();
 
-    let account = fetch_account(&network, &pubkey);
+    let account = cache.fetch(network, &pubkey, commitment, CachedAccountKind::Rewarder);
 
     match account {
-        Ok(value) => {
+        Ok((value, cache_metadata)) => {
             tracing::event!(Level::INFO, "Account fetch successful - Step 1/2");
             let try_wrap = RewarderWrapper::wrap(&value);
 
@@ -28,6 +33,7 @@ pub async fn fetch_rewarder_handler(
                     Ok(Json(FetchRewarderResponse {
                         network_config: payload.network_config,
                         rewarder: value,
+                        cache: cache_metadata,
                     }))
                 }
 
@@ -57,7 +63,12 @@ pub async fn fetch_rewarder_handler(
 ///     "network_config": {
 ///         "variant": "Mainnet"
 ///     },
-///     "rewarder": {...} ~ JSON representation of Rewarder (see RewarderWrapper for format)
+///     "rewarder": {...}, ~ JSON representation of Rewarder (see RewarderWrapper for format)
+///     "cache": {
+///         "hit": true, ~ false when the account was fetched for this request
+///         "slot": 108000000, ~ slot the account data was read at
+///         "age_ms": 1200
+///     }
 /// }
 ///
 /// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
@@ -65,11 +76,12 @@ pub async fn fetch_rewarder_handler(
 pub struct FetchRewarderResponse {
     pub network_config: NetworkConfig,
     pub rewarder: RewarderWrapper,
+    pub cache: CacheMetadata,
 }
 
 /// This type is required because rewarder accounts do not natively implement Serialize and
 /// Deserialize by default which is needed for axum handler response
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RewarderWrapper {
     /// Random pubkey used for generating the program address.
     pub base: Pubkey,
@@ -0,0 +1,252 @@
+use crate::utils::*;
+use crate::{MinerWrapper, QuarryWrapper, RewarderWrapper};
+use anchor_client::anchor_lang::AccountDeserialize;
+use axum::{http::StatusCode, Json};
+use quarry_mint_wrapper::Minter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, program_error::ProgramError};
+use tracing::Level;
+
+/// Compute the total amount of reward tokens owed but not yet claimed by every miner of the
+/// specified rewarder and compare it with the rewarder's remaining mint wrapper allowance
+pub async fn fetch_rewarder_liability_handler(
+    Json(payload): Json<FetchAccountRequest>,
+) -> Result<Json<FetchRewarderLiabilityResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    // Only used to locate the minter, rewarder state is taken from the consistent read below
+    let mint_wrapper = match fetch_account(&network, &pubkey)
+        .ok()
+        .and_then(|account| RewarderWrapper::wrap(&account).ok())
+    {
+        Some(value) => {
+            tracing::event!(Level::INFO, "Account lookup successful - Step 1/4");
+            value.mint_wrapper
+        }
+
+        None => {
+            tracing::event!(Level::ERROR, "Rewarder lookup failed - Step 1/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch rewarder".to_string())),
+            ));
+        }
+    };
+
+    let (minter_key, _bump) = find_minter_address(&mint_wrapper, &pubkey);
+
+    let quarry_keys: Vec<Pubkey> = match fetch_quarries_for_rewarder(&network, &pubkey) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Quarries fetch successful - Step 2/4");
+            value
+                .into_iter()
+                .map(|(quarry_key, _)| quarry_key)
+                .collect()
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Quarries fetch failed - Step 2/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch quarries".to_string())),
+            ));
+        }
+    };
+
+    // Rewarder, minter and quarries are read at one slot, pending rewards are computed at that
+    // slot's Clock timestamp
+    let mut keys = vec![pubkey, minter_key];
+    keys.extend(quarry_keys.iter().copied());
+
+    let read = match fetch_consistent_accounts(&network, &keys, Commitment::default()) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Consistent read successful - Step 3/4");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Consistent read failed - Step 3/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    let rewarder = match read.accounts[0].as_ref().map(RewarderWrapper::wrap) {
+        Some(Ok(value)) => value,
+        _ => {
+            tracing::event!(Level::ERROR, "Rewarder wrap failed - Step 3/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to wrap Rewarder".to_string())),
+            ));
+        }
+    };
+
+    let minter = match read.accounts[1].as_ref().map(deserialize_minter) {
+        Some(Ok(value)) => value,
+        _ => {
+            tracing::event!(Level::ERROR, "Minter deserialize failed - Step 3/4");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to deserialize Minter".to_string())),
+            ));
+        }
+    };
+
+    let now = read.context.unix_timestamp;
+    let mut quarries = Vec::new();
+
+    for (quarry_key, account) in quarry_keys.iter().zip(read.accounts[2..].iter()) {
+        let quarry = match account.as_ref().map(QuarryWrapper::wrap) {
+            Some(Ok(value)) => value,
+            _ => {
+                tracing::event!(Level::ERROR, "Quarry wrap failed - Step 4/4");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Quarry".to_string())),
+                ));
+            }
+        };
+
+        // Miner scans are too large for a single read and may land on a later slot
+        let miner_accounts = match fetch_miners_for_quarry(&network, quarry_key) {
+            Ok(value) => value,
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Miners fetch failed - Step 4/4");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch miners".to_string())),
+                ));
+            }
+        };
+
+        let mut miners = Vec::new();
+
+        for (_miner_key, account) in miner_accounts.iter() {
+            match MinerWrapper::wrap(account) {
+                Ok(value) => miners.push(value),
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Miner wrap failed - Step 4/4");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to wrap Miner".to_string())),
+                    ));
+                }
+            }
+        }
+
+        quarries.push(QuarryLiability::compute(*quarry_key, &quarry, &miners, now));
+    }
+
+    tracing::event!(Level::INFO, "Liability computation successful - Step 4/4");
+
+    let total_pending_rewards: u128 = quarries.iter().map(|q| q.total_pending_rewards).sum();
+    let minter_allowance = minter.allowance;
+
+    Ok(Json(FetchRewarderLiabilityResponse {
+        network_config: payload.network_config,
+        rewarder_key: pubkey,
+        rewards_token_mint: rewarder.rewards_token_mint,
+        computed_at_ts: now,
+        computed_at_slot: read.context.slot,
+        computed_at_max_slot: read.context.max_slot,
+        total_pending_rewards,
+        minter_key,
+        minter_allowance,
+        minter_total_minted: minter.total_minted,
+        allowance_covers_liability: (minter_allowance as u128) >= total_pending_rewards,
+        allowance_surplus: (minter_allowance as i128) - (total_pending_rewards as i128),
+        quarries,
+    }))
+}
+
+// Fetch rewarder liability request is simply a request_utils::FetchAccountRequest object
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "rewarder_key": [...],
+///     "rewards_token_mint": [...],
+///     "computed_at_ts": 1637000000, ~ Clock timestamp at computed_at_slot
+///     "computed_at_slot": 108000000, ~ slot the rewarder, minter and quarries were read at
+///     "computed_at_max_slot": null, ~ latest read slot when they couldn't be read at one slot
+///     "total_pending_rewards": 123456789,
+///     "minter_key": [...],
+///     "minter_allowance": 1000000000,
+///     "minter_total_minted": 500000000,
+///     "allowance_covers_liability": true,
+///     "allowance_surplus": 876543211,
+///     "quarries": [...] ~ Per quarry liability (see QuarryLiability for format)
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchRewarderLiabilityResponse {
+    pub network_config: NetworkConfig,
+    pub rewarder_key: Pubkey,
+    pub rewards_token_mint: Pubkey,
+    /// Unix timestamp the pending rewards were computed at
+    pub computed_at_ts: i64,
+    /// Slot the rewarder, minter and quarries were read at, the earliest one when they were
+    /// read in several requests that landed on different slots
+    pub computed_at_slot: u64,
+    /// Slot of the latest of those requests, None when everything was read at one slot
+    pub computed_at_max_slot: Option<u64>,
+    /// Sum of pending rewards across every miner of every quarry, before claim fees
+    pub total_pending_rewards: u128,
+    /// Mint wrapper Minter account belonging to the rewarder
+    pub minter_key: Pubkey,
+    /// Remaining amount the rewarder is allowed to mint
+    pub minter_allowance: u64,
+    pub minter_total_minted: u64,
+    pub allowance_covers_liability: bool,
+    /// minter_allowance - total_pending_rewards, negative when the allowance falls short
+    pub allowance_surplus: i128,
+    pub quarries: Vec<QuarryLiability>,
+}
+
+/// Pending rewards owed to the miners of a single quarry
+#[derive(Serialize, Deserialize)]
+pub struct QuarryLiability {
+    pub quarry_key: Pubkey,
+    pub token_mint_key: Pubkey,
+    /// Number of miner accounts found onchain for this quarry
+    pub miners_found: u64,
+    pub total_pending_rewards: u128,
+}
+
+impl QuarryLiability {
+    pub fn compute(
+        quarry_key: Pubkey,
+        quarry: &QuarryWrapper,
+        miners: &[MinerWrapper],
+        now: i64,
+    ) -> Self {
+        let total_pending_rewards = miners
+            .iter()
+            .map(|miner| calculate_rewards_earned(quarry, miner, now))
+            .sum();
+
+        Self {
+            quarry_key,
+            token_mint_key: quarry.token_mint_key,
+            miners_found: miners.len() as u64,
+            total_pending_rewards,
+        }
+    }
+}
+
+fn deserialize_minter(account: &Account) -> Result<Minter, ProgramError> {
+    let account_data = account.data.clone();
+    let raw_bytes: &mut &[u8] = &mut &account_data[..];
+
+    Minter::try_deserialize(raw_bytes)
+}
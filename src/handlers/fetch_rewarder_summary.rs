@@ -0,0 +1,327 @@
+use crate::utils::*;
+use crate::{QuarryWrapper, RewarderWrapper};
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Fetch the rewarder specified along with all of its quarries and return aggregate figures
+/// plus a set of consistency checks describing the health of the rewarder. Served from the
+/// index when indexer mode is on.
+pub async fn fetch_rewarder_summary_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchAccountRequest>,
+) -> Result<Json<FetchRewarderSummaryResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    // Rewarders created since the last index run fall through to RPC
+    if let Some((index, snapshot)) = latest_index_snapshot(&index, network) {
+        if let (Ok(Some(rewarder)), Ok(quarries)) = (
+            index.rewarder(&snapshot, &pubkey),
+            index.quarries_for_rewarder(&snapshot, &pubkey),
+        ) {
+            tracing::event!(Level::INFO, "Indexed summary fetch successful - Step 1/1");
+            let summary = RewarderSummary::compute(&rewarder, &quarries, snapshot.timestamp);
+
+            return Ok(Json(FetchRewarderSummaryResponse {
+                network_config: payload.network_config,
+                rewarder_key: pubkey,
+                rewarder,
+                summary,
+                computed_at: ReadContext {
+                    slot: snapshot.slot,
+                    unix_timestamp: snapshot.timestamp,
+                    max_slot: None,
+                },
+                index_status: Some(IndexStatus::new(&snapshot, index.chain_slot(network))),
+            }));
+        }
+    }
+
+    // The quarry scan only discovers which quarries belong to the rewarder, their state is
+    // read together with the rewarder at a single slot below
+    let quarry_keys: Vec<Pubkey> = match fetch_quarries_for_rewarder(&network, &pubkey) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Quarries fetch successful - Step 1/3");
+            value
+                .into_iter()
+                .map(|(quarry_key, _)| quarry_key)
+                .collect()
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Quarries fetch failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch quarries".to_string())),
+            ));
+        }
+    };
+
+    let mut keys = vec![pubkey];
+    keys.extend(quarry_keys.iter().copied());
+
+    let read = match fetch_consistent_accounts(&network, &keys, Commitment::default()) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Consistent read successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Consistent read failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    let rewarder = match read.accounts[0].as_ref().map(RewarderWrapper::wrap) {
+        Some(Ok(value)) => value,
+        _ => {
+            tracing::event!(Level::ERROR, "Rewarder wrap failed - Step 3/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to wrap Rewarder".to_string())),
+            ));
+        }
+    };
+
+    let mut quarries: Vec<(Pubkey, QuarryWrapper)> = Vec::new();
+
+    // Quarries can't be closed, so a missing one is a bug rather than a race
+    for (quarry_key, account) in quarry_keys.iter().zip(read.accounts[1..].iter()) {
+        match account.as_ref().map(QuarryWrapper::wrap) {
+            Some(Ok(value)) => quarries.push((*quarry_key, value)),
+            _ => {
+                tracing::event!(Level::ERROR, "Quarry wrap failed - Step 3/3");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Quarry".to_string())),
+                ));
+            }
+        }
+    }
+
+    tracing::event!(Level::INFO, "Quarry wrap successful - Step 3/3");
+
+    let summary = RewarderSummary::compute(&rewarder, &quarries, read.context.unix_timestamp);
+
+    Ok(Json(FetchRewarderSummaryResponse {
+        network_config: payload.network_config,
+        rewarder_key: pubkey,
+        rewarder,
+        summary,
+        computed_at: read.context,
+        index_status: None,
+    }))
+}
+
+// Fetch rewarder summary request is simply a request_utils::FetchAccountRequest object
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "rewarder_key": [...],
+///     "rewarder": {...} ~ JSON representation of Rewarder (see RewarderWrapper for format)
+///     "summary": {...}, ~ Aggregate figures and health checks (see RewarderSummary for format)
+///     "computed_at": {...}, ~ slot and timestamp everything was read at (see ReadContext)
+///     "index_status": {...} ~ null when served live from RPC (see IndexStatus)
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchRewarderSummaryResponse {
+    pub network_config: NetworkConfig,
+    pub rewarder_key: Pubkey,
+    pub rewarder: RewarderWrapper,
+    pub summary: RewarderSummary,
+    pub computed_at: ReadContext,
+    pub index_status: Option<IndexStatus>,
+}
+
+/// Aggregate view over a [Rewarder] and every [Quarry] it manages
+#[derive(Serialize, Deserialize)]
+pub struct RewarderSummary {
+    /// True when every check passed and no quarry was flagged
+    pub healthy: bool,
+    /// Mirrors [Rewarder]#is_paused
+    pub is_paused: bool,
+    /// Number of quarries found onchain for this rewarder
+    pub quarries_found: u64,
+    /// Sum of [Quarry]#num_miners across all quarries
+    pub total_miners: u64,
+    /// Total tokens staked per staked token mint
+    pub staked_per_mint: Vec<MintStake>,
+    /// How the rewarder's annual rewards are split between its quarries
+    pub emission_split: Vec<QuarryEmission>,
+    /// Rewarder-wide invariant checks
+    pub checks: Vec<SummaryCheck>,
+    /// Quarries that look unhealthy along with the reasons why
+    pub flagged_quarries: Vec<FlaggedQuarry>,
+}
+
+/// Total tokens staked for a single staked token mint
+#[derive(Serialize, Deserialize)]
+pub struct MintStake {
+    pub token_mint_key: Pubkey,
+    pub token_mint_decimals: u8,
+    pub total_tokens_deposited: u64,
+    pub num_quarries: u64,
+}
+
+/// A single quarry's slice of the rewarder's emissions
+#[derive(Serialize, Deserialize)]
+pub struct QuarryEmission {
+    pub quarry_key: Pubkey,
+    pub token_mint_key: Pubkey,
+    pub rewards_share: u64,
+    /// rewards_share / total_rewards_shares, between 0 and 1
+    pub share_of_total: f64,
+    /// Annual rewards rate currently stored on the quarry
+    pub annual_rewards_rate: u64,
+    /// Annual rewards rate the quarry should have given the rewarder's rate and its share
+    pub expected_annual_rewards_rate: u64,
+}
+
+/// Outcome of a single invariant check
+#[derive(Serialize, Deserialize)]
+pub struct SummaryCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// A quarry that failed one or more per-quarry checks
+#[derive(Serialize, Deserialize)]
+pub struct FlaggedQuarry {
+    pub quarry_key: Pubkey,
+    pub reasons: Vec<String>,
+}
+
+impl RewarderSummary {
+    /// Builds the summary from an already wrapped rewarder and its quarries. `now` is the unix
+    /// timestamp used to decide whether a quarry's famine has passed.
+    pub fn compute(
+        rewarder: &RewarderWrapper,
+        quarries: &[(Pubkey, QuarryWrapper)],
+        now: i64,
+    ) -> Self {
+        let mut staked: BTreeMap<Pubkey, MintStake> = BTreeMap::new();
+        let mut emission_split = Vec::new();
+        let mut flagged_quarries = Vec::new();
+        let mut total_miners: u64 = 0;
+        let mut share_sum: u128 = 0;
+
+        for (quarry_key, quarry) in quarries.iter() {
+            total_miners = total_miners.saturating_add(quarry.num_miners);
+            share_sum += quarry.rewards_share as u128;
+
+            let entry = staked.entry(quarry.token_mint_key).or_insert(MintStake {
+                token_mint_key: quarry.token_mint_key,
+                token_mint_decimals: quarry.token_mint_decimals,
+                total_tokens_deposited: 0,
+                num_quarries: 0,
+            });
+            entry.total_tokens_deposited = entry
+                .total_tokens_deposited
+                .saturating_add(quarry.total_tokens_deposited);
+            entry.num_quarries += 1;
+
+            let expected_annual_rewards_rate = expected_quarry_rate(rewarder, quarry);
+
+            emission_split.push(QuarryEmission {
+                quarry_key: *quarry_key,
+                token_mint_key: quarry.token_mint_key,
+                rewards_share: quarry.rewards_share,
+                share_of_total: if rewarder.total_rewards_shares == 0 {
+                    0.0
+                } else {
+                    quarry.rewards_share as f64 / rewarder.total_rewards_shares as f64
+                },
+                annual_rewards_rate: quarry.annual_rewards_rate,
+                expected_annual_rewards_rate,
+            });
+
+            let mut reasons = Vec::new();
+
+            if quarry.rewards_share == 0 {
+                reasons.push("Quarry has a rewards share of zero".to_string());
+            }
+
+            if quarry.famine_ts <= now {
+                reasons.push(format!("Quarry famine passed at {}", quarry.famine_ts));
+            }
+
+            if quarry.annual_rewards_rate != expected_annual_rewards_rate {
+                reasons.push(format!(
+                    "Quarry annual rewards rate is stale: stored {}, expected {}",
+                    quarry.annual_rewards_rate, expected_annual_rewards_rate
+                ));
+            }
+
+            if !reasons.is_empty() {
+                flagged_quarries.push(FlaggedQuarry {
+                    quarry_key: *quarry_key,
+                    reasons,
+                });
+            }
+        }
+
+        let checks = vec![
+            SummaryCheck {
+                name: "rewards_share_sum".to_string(),
+                passed: share_sum == rewarder.total_rewards_shares as u128,
+                detail: format!(
+                    "Sum of quarry rewards shares is {}, rewarder total_rewards_shares is {}",
+                    share_sum, rewarder.total_rewards_shares
+                ),
+            },
+            SummaryCheck {
+                name: "quarry_count".to_string(),
+                passed: quarries.len() as u64 == rewarder.num_quarries as u64,
+                detail: format!(
+                    "Found {} quarries, rewarder num_quarries is {}",
+                    quarries.len(),
+                    rewarder.num_quarries
+                ),
+            },
+            SummaryCheck {
+                name: "not_paused".to_string(),
+                passed: !rewarder.is_paused,
+                detail: format!("Rewarder is_paused is {}", rewarder.is_paused),
+            },
+        ];
+
+        let healthy = checks.iter().all(|c| c.passed) && flagged_quarries.is_empty();
+
+        Self {
+            healthy,
+            is_paused: rewarder.is_paused,
+            quarries_found: quarries.len() as u64,
+            total_miners,
+            staked_per_mint: staked.into_values().collect(),
+            emission_split,
+            checks,
+            flagged_quarries,
+        }
+    }
+}
+
+/// Mirrors the rate computation done onchain by Quarry::update_rewards_internal
+pub fn expected_quarry_rate(rewarder: &RewarderWrapper, quarry: &QuarryWrapper) -> u64 {
+    if rewarder.total_rewards_shares == 0 {
+        return 0;
+    }
+
+    ((rewarder.annual_rewards_rate as u128) * (quarry.rewards_share as u128)
+        / (rewarder.total_rewards_shares as u128)) as u64
+}
@@ -0,0 +1,41 @@
+use crate::utils::{network_utils::NetworkConfig, EndpointStatus, ENDPOINT_POOL};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+/// Report the health of every RPC endpoint configured for the specified network, in the order
+/// requests currently try them
+pub async fn fetch_rpc_health_handler(
+    Json(payload): Json<NetworkConfig>,
+) -> Json<FetchRpcHealthResponse> {
+    let network = payload.variant;
+    let ranked = ENDPOINT_POOL.ranked_urls(network);
+    let mut endpoints = ENDPOINT_POOL.status(network);
+
+    endpoints.sort_by_key(|e| ranked.iter().position(|url| *url == e.url));
+    tracing::event!(Level::INFO, "RPC health fetch successful");
+
+    Json(FetchRpcHealthResponse {
+        network_config: payload,
+        endpoints,
+    })
+}
+
+// Fetch rpc health request is simply a network_utils::NetworkConfig object
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "endpoints": [
+///         {...}, ~ the endpoint requests are routed to (see EndpointStatus)
+///         ...
+///     ]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchRpcHealthResponse {
+    pub network_config: NetworkConfig,
+    pub endpoints: Vec<EndpointStatus>,
+}
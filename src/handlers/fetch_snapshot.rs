@@ -0,0 +1,328 @@
+use crate::utils::*;
+use crate::MinerEntry;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::collections::{BTreeMap, HashMap};
+use tracing::Level;
+
+/// Snapshot every staker of a quarry or rewarder, weight their balances and export the
+/// result as CSV and as a merkle-distributor tree
+pub async fn fetch_snapshot_handler(
+    Json(payload): Json<FetchSnapshotRequest>,
+) -> Result<Json<FetchSnapshotResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    // Every scan reports the slot it was served at, the snapshot covers their range
+    let mut slots: Vec<u64> = Vec::new();
+
+    let quarry_keys: Vec<Pubkey> = match payload.target {
+        SnapshotTarget::Quarry => vec![pubkey],
+        SnapshotTarget::Rewarder => {
+            match fetch_quarries_for_rewarder_with_slot(&network, &pubkey) {
+                Ok((value, slot)) => {
+                    slots.push(slot);
+                    value.into_iter().map(|(key, _account)| key).collect()
+                }
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Quarries fetch failed - Step 1/2");
+                    return Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(Value::String("Failed to fetch quarries".to_string())),
+                    ));
+                }
+            }
+        }
+    };
+
+    tracing::event!(Level::INFO, "Quarry resolution successful - Step 1/2");
+
+    let mut miners: Vec<MinerEntry> = Vec::new();
+
+    for quarry_key in quarry_keys.iter() {
+        let accounts = match fetch_miners_for_quarry_with_slot(&network, quarry_key) {
+            Ok((value, slot)) => {
+                slots.push(slot);
+                value
+            }
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Miners fetch failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to fetch miners".to_string())),
+                ));
+            }
+        };
+
+        match MinerEntry::wrap_all(&accounts) {
+            Ok(value) => miners.extend(value),
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Miner wrap failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to wrap Miner".to_string())),
+                ));
+            }
+        }
+    }
+
+    tracing::event!(Level::INFO, "Miner fetch successful - Step 2/2");
+
+    let claimants = SnapshotClaimant::compute(&miners, &payload);
+    let csv = SnapshotClaimant::to_csv(&claimants);
+    let merkle_distributor = MerkleDistributorTree::build(&claimants);
+
+    Ok(Json(FetchSnapshotResponse {
+        network_config: payload.network_config,
+        target: payload.target,
+        target_key: pubkey,
+        min_slot: slots.iter().min().copied().unwrap_or_default(),
+        max_slot: slots.iter().max().copied().unwrap_or_default(),
+        claimants,
+        csv,
+        merkle_distributor,
+    }))
+}
+
+/// Which kind of account the snapshot request's pubkey points at
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SnapshotTarget {
+    Quarry,
+    Rewarder,
+}
+
+/// How a miner's balance is turned into a weight
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SnapshotWeighting {
+    /// Weight is the balance itself
+    Linear,
+    /// Weight is the square root of the balance, dampening whales
+    SquareRoot,
+    /// Every eligible authority gets the same weight, scaled by the highest multiplier among
+    /// the quarries it stakes in
+    Equal,
+}
+
+/// Multiplier applied to the weights of miners in a given quarry
+#[derive(Serialize, Deserialize)]
+pub struct QuarryWeight {
+    pub quarry_key: Pubkey,
+    pub multiplier: f64,
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ quarry or rewarder pubkey
+///     },
+///     "target": "Rewarder", ~ "Quarry" or "Rewarder"
+///     "weighting": "Linear", ~ optional, "Linear" (default), "SquareRoot" or "Equal"
+///     "quarry_weights": [{ "quarry_key": [...], "multiplier": 2.0 }], ~ optional, defaults to 1
+///     "min_balance": 1000, ~ optional, miners below this balance are skipped
+///     "airdrop_amount": 1000000000 ~ optional, amount to split between claimants by weight
+/// }
+///
+/// When airdrop_amount is omitted each claimant's amount is simply their (rounded) weight.
+#[derive(Serialize, Deserialize)]
+pub struct FetchSnapshotRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub target: SnapshotTarget,
+    pub weighting: Option<SnapshotWeighting>,
+    pub quarry_weights: Option<Vec<QuarryWeight>>,
+    pub min_balance: Option<u64>,
+    pub airdrop_amount: Option<u64>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "target": "Rewarder",
+///     "target_key": [...],
+///     "min_slot": 108000000, ~ slot of the earliest scan
+///     "max_slot": 108000012, ~ slot of the latest scan, equal to min_slot for a single scan
+///     "claimants": [...], ~ see SnapshotClaimant
+///     "csv": "authority,balance,weight,amount\n...",
+///     "merkle_distributor": {...} ~ see MerkleDistributorTree
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchSnapshotResponse {
+    pub network_config: NetworkConfig,
+    pub target: SnapshotTarget,
+    pub target_key: Pubkey,
+    /// Lowest context slot among the quarry and miner scans
+    pub min_slot: u64,
+    /// Highest context slot among the quarry and miner scans
+    pub max_slot: u64,
+    pub claimants: Vec<SnapshotClaimant>,
+    pub csv: String,
+    pub merkle_distributor: MerkleDistributorTree,
+}
+
+/// A single miner authority aggregated across every miner it owns in the snapshot
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotClaimant {
+    pub authority: Pubkey,
+    /// Sum of raw balances across the authority's eligible miners
+    pub balance: u128,
+    pub weight: f64,
+    /// Amount allocated to the claimant in the merkle tree
+    pub amount: u64,
+}
+
+impl SnapshotClaimant {
+    /// Applies the request's thresholds and weighting to the miners and aggregates them per
+    /// authority. Claimants are sorted by their base58 address and claimants that end up
+    /// with an amount of zero are dropped.
+    pub fn compute(miners: &[MinerEntry], request: &FetchSnapshotRequest) -> Vec<Self> {
+        let weighting = request.weighting.unwrap_or(SnapshotWeighting::Linear);
+        let min_balance = request.min_balance.unwrap_or(0);
+        let multipliers: HashMap<Pubkey, f64> = request
+            .quarry_weights
+            .as_ref()
+            .map(|weights| {
+                weights
+                    .iter()
+                    .map(|w| (w.quarry_key, w.multiplier))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut per_authority: BTreeMap<Pubkey, (u128, f64)> = BTreeMap::new();
+
+        for entry in miners.iter() {
+            let miner = &entry.miner;
+
+            if miner.balance == 0 || miner.balance < min_balance {
+                continue;
+            }
+
+            let multiplier = multipliers.get(&miner.quarry_key).copied().unwrap_or(1.0);
+            let totals = per_authority.entry(miner.authority).or_insert((0, 0.0));
+            totals.0 += miner.balance as u128;
+
+            match weighting {
+                SnapshotWeighting::Linear => totals.1 += miner.balance as f64 * multiplier,
+                SnapshotWeighting::SquareRoot => {
+                    totals.1 += (miner.balance as f64).sqrt() * multiplier
+                }
+                // One weight per authority no matter how many quarries it stakes in
+                SnapshotWeighting::Equal => totals.1 = totals.1.max(multiplier),
+            }
+        }
+
+        let total_weight: f64 = per_authority.values().map(|(_, w)| *w).sum();
+
+        let mut claimants: Vec<Self> = per_authority
+            .into_iter()
+            .map(|(authority, (balance, weight))| {
+                let amount = match request.airdrop_amount {
+                    Some(total) if total_weight > 0.0 => {
+                        ((total as f64) * weight / total_weight).floor() as u64
+                    }
+                    Some(_) => 0,
+                    None => weight.round() as u64,
+                };
+
+                Self {
+                    authority,
+                    balance,
+                    weight,
+                    amount,
+                }
+            })
+            .filter(|c| c.amount > 0)
+            .collect();
+
+        claimants.sort_by_key(|c| c.authority.to_string());
+        claimants
+    }
+
+    /// Renders the claimants as CSV with a header row
+    pub fn to_csv(claimants: &[Self]) -> String {
+        let mut csv = String::from("authority,balance,weight,amount\n");
+
+        for c in claimants.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                c.authority, c.balance, c.weight, c.amount
+            ));
+        }
+
+        csv
+    }
+}
+
+/// Output in the format consumed by the merkle-distributor tooling. Hashes and amounts are
+/// 0x prefixed hex strings and claims are keyed by base58 address.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleDistributorTree {
+    pub merkle_root: String,
+    pub token_total: String,
+    pub num_nodes: u64,
+    pub claims: BTreeMap<String, MerkleDistributorClaim>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MerkleDistributorClaim {
+    pub index: u64,
+    pub amount: String,
+    pub proof: Vec<String>,
+}
+
+impl MerkleDistributorTree {
+    /// Builds the tree from claimants that are already sorted, using their position as the
+    /// claim index
+    pub fn build(claimants: &[SnapshotClaimant]) -> Self {
+        let leaves: Vec<[u8; 32]> = claimants
+            .iter()
+            .enumerate()
+            .map(|(i, c)| merkle_leaf(i as u64, &c.authority, c.amount))
+            .collect();
+
+        let tree = MerkleTree::new(&leaves);
+        let token_total: u128 = claimants.iter().map(|c| c.amount as u128).sum();
+
+        let claims = claimants
+            .iter()
+            .zip(leaves.iter())
+            .enumerate()
+            .map(|(i, (c, leaf))| {
+                let proof = tree
+                    .proof(leaf)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|node| format!("0x{}", hex::encode(node)))
+                    .collect();
+
+                (
+                    c.authority.to_string(),
+                    MerkleDistributorClaim {
+                        index: i as u64,
+                        amount: format!("0x{:x}", c.amount),
+                        proof,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            merkle_root: format!("0x{}", hex::encode(tree.root())),
+            token_total: format!("0x{:x}", token_total),
+            num_nodes: claimants.len() as u64,
+            claims,
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use crate::utils::*;
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Range covered when the request doesn't specify a start, in seconds before end_ts
+pub const DEFAULT_SERIES_RANGE: i64 = 30 * 86_400;
+
+/// Chart how a quarry changed over time from the indexed snapshots
+pub async fn fetch_quarry_timeseries_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchTimeseriesRequest>,
+) -> Result<Json<FetchQuarryTimeseriesResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+    let (index, snapshots) =
+        sampled_snapshots(&index, network, series_range(&payload), payload.resolution)?;
+
+    let mut points = Vec::with_capacity(snapshots.len());
+
+    for snapshot in snapshots.iter() {
+        let quarry = match index.quarry(snapshot, &pubkey) {
+            Ok(value) => value,
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Indexed quarry fetch failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to read index".to_string())),
+                ));
+            }
+        };
+
+        // Skip runs from before the quarry existed
+        if let Some(quarry) = quarry {
+            points.push(QuarrySeriesPoint {
+                timestamp: snapshot.timestamp,
+                slot: snapshot.slot,
+                total_tokens_deposited: quarry.total_tokens_deposited,
+                num_miners: quarry.num_miners,
+                rewards_share: quarry.rewards_share,
+                annual_rewards_rate: quarry.annual_rewards_rate,
+                effective_rewards_rate: effective_rewards_rate(&quarry, snapshot.timestamp),
+            });
+        }
+    }
+
+    tracing::event!(Level::INFO, "Quarry series build successful - Step 2/2");
+
+    Ok(Json(FetchQuarryTimeseriesResponse {
+        network_config: payload.network_config,
+        quarry_key: pubkey,
+        resolution: payload.resolution,
+        points,
+    }))
+}
+
+/// Chart how a rewarder and its quarries changed over time from the indexed snapshots
+pub async fn fetch_rewarder_timeseries_handler(
+    Extension(index): Extension<Option<Arc<QuarryIndex>>>,
+    Json(payload): Json<FetchTimeseriesRequest>,
+) -> Result<Json<FetchRewarderTimeseriesResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+    let (index, snapshots) =
+        sampled_snapshots(&index, network, series_range(&payload), payload.resolution)?;
+
+    let mut points = Vec::with_capacity(snapshots.len());
+
+    for snapshot in snapshots.iter() {
+        let indexed = index.rewarder(snapshot, &pubkey).and_then(|rewarder| {
+            index
+                .quarries_for_rewarder(snapshot, &pubkey)
+                .map(|quarries| (rewarder, quarries))
+        });
+
+        let (rewarder, quarries) = match indexed {
+            Ok(value) => value,
+            Err(_e) => {
+                tracing::event!(Level::ERROR, "Indexed rewarder fetch failed - Step 2/2");
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(Value::String("Failed to read index".to_string())),
+                ));
+            }
+        };
+
+        // Skip runs from before the rewarder existed
+        if let Some(rewarder) = rewarder {
+            points.push(RewarderSeriesPoint {
+                timestamp: snapshot.timestamp,
+                slot: snapshot.slot,
+                num_quarries: rewarder.num_quarries,
+                num_miners: quarries.iter().map(|(_, q)| q.num_miners).sum(),
+                total_rewards_shares: rewarder.total_rewards_shares,
+                annual_rewards_rate: rewarder.annual_rewards_rate,
+                effective_rewards_rate: quarries
+                    .iter()
+                    .map(|(_, q)| effective_rewards_rate(q, snapshot.timestamp))
+                    .sum(),
+            });
+        }
+    }
+
+    tracing::event!(Level::INFO, "Rewarder series build successful - Step 2/2");
+
+    Ok(Json(FetchRewarderTimeseriesResponse {
+        network_config: payload.network_config,
+        rewarder_key: pubkey,
+        resolution: payload.resolution,
+        points,
+    }))
+}
+
+/// Resolves the requested range, defaulting to the DEFAULT_SERIES_RANGE ending now
+pub fn series_range(payload: &FetchTimeseriesRequest) -> (i64, i64) {
+    let end_ts = payload.end_ts.unwrap_or_else(current_unix_timestamp);
+    let start_ts = payload.start_ts.unwrap_or(end_ts - DEFAULT_SERIES_RANGE);
+
+    (start_ts, end_ts)
+}
+
+/// Loads the runs in the range, keeping the last one of every resolution bucket
+pub fn sampled_snapshots(
+    index: &Option<Arc<QuarryIndex>>,
+    network: Network,
+    (start_ts, end_ts): (i64, i64),
+    resolution: SeriesResolution,
+) -> Result<(Arc<QuarryIndex>, Vec<IndexSnapshot>), (StatusCode, Json<serde_json::Value>)> {
+    let index = match index {
+        Some(value) => value.clone(),
+        None => {
+            tracing::event!(Level::ERROR, "Indexer mode disabled - Step 1/2");
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(Value::String("Indexer mode is not enabled".to_string())),
+            ));
+        }
+    };
+
+    match index.snapshots_in_range(network, start_ts, end_ts) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Snapshot range fetch successful - Step 1/2");
+            Ok((index, sample_snapshots(value, resolution)))
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Snapshot range fetch failed - Step 1/2");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to read index".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ the quarry or rewarder to chart
+///     },
+///     "resolution": "Daily", ~ "Hourly" or "Daily"
+///     "start_ts": 1635000000, ~ optional, defaults to 30 days before end_ts
+///     "end_ts": 1637000000 ~ optional, defaults to now
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchTimeseriesRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub resolution: SeriesResolution,
+    pub start_ts: Option<i64>,
+    pub end_ts: Option<i64>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "quarry_key": [...],
+///     "resolution": "Daily",
+///     "points": [...] ~ oldest first, see QuarrySeriesPoint
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchQuarryTimeseriesResponse {
+    pub network_config: NetworkConfig,
+    pub quarry_key: Pubkey,
+    pub resolution: SeriesResolution,
+    pub points: Vec<QuarrySeriesPoint>,
+}
+
+/// State of a quarry at the last indexed run of a bucket
+#[derive(Serialize, Deserialize)]
+pub struct QuarrySeriesPoint {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub total_tokens_deposited: u64,
+    pub num_miners: u64,
+    pub rewards_share: u64,
+    pub annual_rewards_rate: u64,
+    /// annual_rewards_rate, or zero past famine or while nothing is staked
+    pub effective_rewards_rate: u64,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "rewarder_key": [...],
+///     "resolution": "Daily",
+///     "points": [...] ~ oldest first, see RewarderSeriesPoint
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct FetchRewarderTimeseriesResponse {
+    pub network_config: NetworkConfig,
+    pub rewarder_key: Pubkey,
+    pub resolution: SeriesResolution,
+    pub points: Vec<RewarderSeriesPoint>,
+}
+
+/// State of a rewarder and its quarries at the last indexed run of a bucket
+#[derive(Serialize, Deserialize)]
+pub struct RewarderSeriesPoint {
+    pub timestamp: i64,
+    pub slot: u64,
+    pub num_quarries: u16,
+    /// Sum of num_miners across the rewarder's quarries
+    pub num_miners: u64,
+    pub total_rewards_shares: u64,
+    pub annual_rewards_rate: u64,
+    /// Sum of the effective rates of the rewarder's quarries
+    pub effective_rewards_rate: u64,
+}
@@ -0,0 +1,234 @@
+use crate::utils::*;
+use axum::extract::Extension;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::sync::Arc;
+use tracing::Level;
+
+/// Register a webhook that is POSTed a signed notification whenever one of its conditions on
+/// a Quarry, Miner or Rewarder account fires
+pub async fn register_webhook_handler(
+    Extension(store): Extension<Arc<WebhookStore>>,
+    Json(payload): Json<RegisterWebhookRequest>,
+) -> Result<Json<RegisterWebhookResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let pubkey = payload.pubkey_config.pubkey;
+
+    if !webhook_url_allowed(&payload.url).await {
+        tracing::event!(Level::ERROR, "Webhook url invalid - Step 1/3");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String(
+                "url must be an http(s) url resolving to public addresses".to_string(),
+            )),
+        ));
+    }
+
+    if payload.conditions.is_empty() {
+        tracing::event!(Level::ERROR, "Webhook has no conditions - Step 1/3");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String(
+                "At least one condition is required".to_string(),
+            )),
+        ));
+    }
+
+    match fetch_account(&network, &pubkey)
+        .ok()
+        .as_ref()
+        .and_then(QuarryAccount::decode)
+    {
+        Some(_) => {
+            tracing::event!(Level::INFO, "Watched account decode successful - Step 2/3");
+        }
+
+        None => {
+            tracing::event!(Level::ERROR, "Watched account decode failed - Step 2/3");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "pubkey must be an existing quarry, miner or rewarder".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let webhook = Webhook {
+        id: generate_webhook_id(),
+        url: payload.url,
+        secret: payload.secret.unwrap_or_else(generate_webhook_id),
+        network,
+        pubkey,
+        conditions: payload.conditions,
+        created_at: current_unix_timestamp(),
+    };
+
+    match store.insert(webhook.clone()) {
+        Ok(_) => {
+            tracing::event!(Level::INFO, "Webhook registration successful - Step 3/3");
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Webhook persist failed - Step 3/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to save webhook".to_string())),
+            ));
+        }
+    };
+
+    Ok(Json(RegisterWebhookResponse {
+        network_config: payload.network_config,
+        webhook,
+    }))
+}
+
+/// List every registered webhook, without their secrets
+pub async fn list_webhooks_handler(
+    Extension(store): Extension<Arc<WebhookStore>>,
+) -> Json<ListWebhooksResponse> {
+    tracing::event!(Level::INFO, "Webhook listing successful");
+
+    Json(ListWebhooksResponse {
+        webhooks: store.list().iter().map(WebhookSummary::from).collect(),
+    })
+}
+
+/// Remove a registered webhook, given the secret it was registered with
+pub async fn delete_webhook_handler(
+    Extension(store): Extension<Arc<WebhookStore>>,
+    Json(payload): Json<DeleteWebhookRequest>,
+) -> Result<Json<DeleteWebhookResponse>, (StatusCode, Json<serde_json::Value>)> {
+    match store.remove(&payload.id, &payload.secret) {
+        Ok(true) => {
+            tracing::event!(Level::INFO, "Webhook removal successful");
+            Ok(Json(DeleteWebhookResponse { id: payload.id }))
+        }
+
+        Ok(false) => {
+            tracing::event!(Level::ERROR, "Webhook not found");
+            Err((
+                StatusCode::NOT_FOUND,
+                Json(Value::String("Webhook not found".to_string())),
+            ))
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Webhook persist failed");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to save webhooks".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ the quarry, miner or rewarder to watch
+///     },
+///     "url": "https://ops.example.com/hooks/quarry",
+///     "secret": "s3cr3t", ~ optional, generated when omitted
+///     "conditions": [
+///         { "field": "is_paused", "operator": "Changed" },
+///         { "field": "pending_authority", "operator": "IsSet" },
+///         { "field": "famine_ts", "operator": "WithinSeconds", "value": 86400 },
+///         { "field": "balance", "operator": "LessThan", "value": 1000000 }
+///     ]
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct RegisterWebhookRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub url: String,
+    pub secret: Option<String>,
+    pub conditions: Vec<WebhookCondition>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "webhook": {
+///         "id": "9f2c...",
+///         "url": "https://ops.example.com/hooks/quarry",
+///         "secret": "s3cr3t", ~ only ever returned here
+///         "network": "Mainnet",
+///         "pubkey": [...],
+///         "conditions": [...],
+///         "created_at": 1637000000
+///     }
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct RegisterWebhookResponse {
+    pub network_config: NetworkConfig,
+    pub webhook: Webhook,
+}
+
+/// Example response
+///
+/// {
+///     "webhooks": [...] ~ see WebhookSummary
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct ListWebhooksResponse {
+    pub webhooks: Vec<WebhookSummary>,
+}
+
+/// A registered webhook without its secret
+#[derive(Serialize, Deserialize)]
+pub struct WebhookSummary {
+    pub id: String,
+    pub url: String,
+    pub network: Network,
+    pub pubkey: Pubkey,
+    pub conditions: Vec<WebhookCondition>,
+    pub created_at: i64,
+}
+
+impl From<&Webhook> for WebhookSummary {
+    fn from(webhook: &Webhook) -> Self {
+        Self {
+            id: webhook.id.clone(),
+            url: webhook.url.clone(),
+            network: webhook.network,
+            pubkey: webhook.pubkey,
+            conditions: webhook.conditions.clone(),
+            created_at: webhook.created_at,
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "id": "9f2c...",
+///     "secret": "s3cr3t" ~ the secret the webhook was registered with
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeleteWebhookRequest {
+    pub id: String,
+    pub secret: String,
+}
+
+/// Example response
+///
+/// {
+///     "id": "9f2c..."
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct DeleteWebhookResponse {
+    pub id: String,
+}
@@ -1,11 +1,53 @@
+pub mod build_admin_transaction;
+pub mod build_user_transaction;
+pub mod decode_transaction;
+pub mod derive_address;
+pub mod export_claims;
+pub mod fetch_activity;
+pub mod fetch_apr_history;
 pub mod fetch_blockheight;
+pub mod fetch_filtered_accounts;
 pub mod fetch_miner;
+pub mod fetch_miner_leaderboard;
+pub mod fetch_miner_rewards;
 pub mod fetch_miners_for_quarry;
+pub mod fetch_position_history;
 pub mod fetch_quarry;
 pub mod fetch_rewarder;
+pub mod fetch_rewarder_liability;
+pub mod fetch_rewarder_summary;
+pub mod fetch_rpc_health;
+pub mod fetch_snapshot;
+pub mod fetch_timeseries;
+pub mod manage_webhooks;
+pub mod query_accounts;
+pub mod simulate_transaction;
+pub mod stream_activity;
+pub mod subscribe_accounts;
 
+pub use build_admin_transaction::*;
+pub use build_user_transaction::*;
+pub use decode_transaction::*;
+pub use derive_address::*;
+pub use export_claims::*;
+pub use fetch_activity::*;
+pub use fetch_apr_history::*;
 pub use fetch_blockheight::*;
+pub use fetch_filtered_accounts::*;
 pub use fetch_miner::*;
+pub use fetch_miner_leaderboard::*;
+pub use fetch_miner_rewards::*;
 pub use fetch_miners_for_quarry::*;
+pub use fetch_position_history::*;
 pub use fetch_quarry::*;
 pub use fetch_rewarder::*;
+pub use fetch_rewarder_liability::*;
+pub use fetch_rewarder_summary::*;
+pub use fetch_rpc_health::*;
+pub use fetch_snapshot::*;
+pub use fetch_timeseries::*;
+pub use manage_webhooks::*;
+pub use query_accounts::*;
+pub use simulate_transaction::*;
+pub use stream_activity::*;
+pub use subscribe_accounts::*;
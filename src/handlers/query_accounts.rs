@@ -0,0 +1,155 @@
+use crate::utils::*;
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::commitment_config::CommitmentLevel;
+use tracing::Level;
+
+/// Query a collection of quarry_mine accounts with predicates on any wrapper field, sorting
+/// and cursor pagination. Equality predicates narrow the RPC scan, everything else is
+/// evaluated by warlock on the decoded accounts.
+pub async fn query_accounts_handler(
+    Json(payload): Json<QueryAccountsRequest>,
+) -> Result<Json<QueryAccountsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+    let account_type = payload.account_type;
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_QUERY_LIMIT)
+        .clamp(1, MAX_QUERY_LIMIT);
+
+    let query = AccountQuery::new(
+        account_type,
+        &payload.predicates,
+        payload.sort.as_ref(),
+        payload.fields.as_deref(),
+        current_unix_timestamp(),
+    );
+
+    let built = query.and_then(|query| {
+        let filters = query.memcmp_filters()?;
+        let cursor = match &payload.cursor {
+            Some(cursor) => Some(query.parse_cursor(cursor)?),
+            None => None,
+        };
+
+        Ok((query, filters, cursor))
+    });
+
+    let (query, filters, cursor) = match built {
+        Ok(value) => value,
+        Err(message) => {
+            tracing::event!(Level::ERROR, "Query build failed - Step 1/3");
+            return Err((StatusCode::BAD_REQUEST, Json(Value::String(message))));
+        }
+    };
+
+    // An unfiltered miner scan returns every miner of every quarry
+    if account_type == AccountType::Miner && filters.field_filter_count() == 0 {
+        tracing::event!(Level::ERROR, "Unfiltered miner query requested - Step 1/3");
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(Value::String(
+                "Miner queries need at least one Equals predicate".to_string(),
+            )),
+        ));
+    }
+
+    let accounts = match fetch_program_accounts_sliced(
+        &network,
+        &quarry_mine::ID,
+        Some(filters.build()),
+        Some(CommitmentLevel::Confirmed),
+        Some(query.projection().data_slice()),
+    ) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Query scan successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Query scan failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    match query.execute(&accounts, cursor.as_ref(), limit) {
+        Ok(page) => {
+            tracing::event!(Level::INFO, "Query execution successful - Step 3/3");
+            Ok(Json(QueryAccountsResponse {
+                network_config: payload.network_config,
+                account_type,
+                total_matches: page.total_matches,
+                next_cursor: page.next_cursor,
+                accounts: page.accounts,
+            }))
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Query execution failed - Step 3/3");
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to decode accounts".to_string())),
+            ))
+        }
+    }
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "account_type": "Miner", ~ "Quarry", "Miner" or "Rewarder"
+///     "predicates": [ ~ every predicate must hold, see QueryPredicate
+///         { "field": "quarry_key", "operator": "Equals", "value": [...] },
+///         { "field": "balance", "operator": "GreaterThan", "value": 1e9 }
+///     ],
+///     "sort": { "field": "balance", "direction": "Descending" }, ~ optional, see QuerySort
+///     "fields": ["authority", "balance"], ~ optional, defaults to every field
+///     "cursor": "1500000000:9xQe...", ~ optional, next_cursor of the previous page
+///     "limit": 50 ~ optional, defaults to 100, at most 1000
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct QueryAccountsRequest {
+    pub network_config: NetworkConfig,
+    pub account_type: AccountType,
+    #[serde(default)]
+    pub predicates: Vec<QueryPredicate>,
+    pub sort: Option<QuerySort>,
+    pub fields: Option<Vec<String>>,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "account_type": "Miner",
+///     "total_matches": 1234, ~ accounts matching every predicate, across all pages
+///     "next_cursor": "1500000000:9xQe...", ~ null on the last page
+///     "accounts": [
+///         {
+///             "pubkey": [...],
+///             "account": {...} ~ the requested fields of the account
+///         },
+///         ...
+///     ]
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct QueryAccountsResponse {
+    pub network_config: NetworkConfig,
+    pub account_type: AccountType,
+    pub total_matches: u64,
+    pub next_cursor: Option<String>,
+    pub accounts: Vec<QueryEntry>,
+}
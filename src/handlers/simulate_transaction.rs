@@ -0,0 +1,221 @@
+use crate::utils::*;
+use crate::{MinerWrapper, QuarryWrapper};
+use axum::{http::StatusCode, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{account::Account, transaction::Transaction};
+use tracing::Level;
+
+/// Simulate a transaction touching Quarry accounts and return how the affected Miner, Quarry
+/// and token accounts would change if it landed
+pub async fn simulate_transaction_handler(
+    Json(payload): Json<SimulateTransactionRequest>,
+) -> Result<Json<SimulateTransactionResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let network = payload.network_config.variant;
+
+    let transaction: Transaction = match base64::decode(&payload.transaction)
+        .ok()
+        .and_then(|bytes| bincode::deserialize(&bytes).ok())
+    {
+        Some(value) => value,
+        None => {
+            tracing::event!(Level::ERROR, "Transaction decode failed - Step 1/3");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "transaction must be a base64 encoded, bincode serialized transaction"
+                        .to_string(),
+                )),
+            ));
+        }
+    };
+
+    let keys = transaction.message.account_keys.clone();
+
+    let pre_accounts = match fetch_multiple_accounts(&network, &keys) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Account fetch successful - Step 1/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Account fetch failed - Step 1/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to fetch accounts".to_string())),
+            ));
+        }
+    };
+
+    let simulation = match simulate_transaction(&network, &transaction, &keys) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Transaction simulation successful - Step 2/3");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Transaction simulation failed - Step 2/3");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String("Failed to simulate transaction".to_string())),
+            ));
+        }
+    };
+
+    // A failed simulation returns no account states, which would read as every account
+    // being closed, so only the error and logs are reported
+    let post_accounts: Vec<Option<Account>> = match (&simulation.err, &simulation.accounts) {
+        (None, Some(accounts)) => accounts
+            .iter()
+            .map(|a| a.as_ref().and_then(|ui| ui.decode::<Account>()))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut miners = Vec::new();
+    let mut quarries = Vec::new();
+    let mut token_balance_changes = Vec::new();
+
+    for (i, (key, post)) in keys.iter().zip(post_accounts).enumerate() {
+        let pre = pre_accounts.get(i).cloned().flatten();
+        let owner = post.as_ref().or(pre.as_ref()).map(|a| a.owner);
+
+        if owner == Some(quarry_mine::ID) {
+            let pre_miner = pre.as_ref().and_then(|a| MinerWrapper::wrap(a).ok());
+            let post_miner = post.as_ref().and_then(|a| MinerWrapper::wrap(a).ok());
+
+            if pre_miner.is_some() || post_miner.is_some() {
+                miners.push(AccountDiff::new(*key, pre_miner, post_miner));
+                continue;
+            }
+
+            let pre_quarry = pre.as_ref().and_then(|a| QuarryWrapper::wrap(a).ok());
+            let post_quarry = post.as_ref().and_then(|a| QuarryWrapper::wrap(a).ok());
+
+            if pre_quarry.is_some() || post_quarry.is_some() {
+                quarries.push(AccountDiff::new(*key, pre_quarry, post_quarry));
+            }
+        } else if owner == Some(spl_token::ID) {
+            if let Some(change) = TokenBalanceChange::compute(*key, pre.as_ref(), post.as_ref()) {
+                token_balance_changes.push(change);
+            }
+        }
+    }
+
+    tracing::event!(Level::INFO, "Simulation decoding successful - Step 3/3");
+
+    Ok(Json(SimulateTransactionResponse {
+        network_config: payload.network_config,
+        success: simulation.err.is_none(),
+        err: simulation.err.map(|e| format!("{:?}", e)),
+        logs: simulation.logs.unwrap_or_default(),
+        miners,
+        quarries,
+        token_balance_changes,
+    }))
+}
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "transaction": "AQAAAA..." ~ base64 encoded transaction, e.g. from the /tx endpoints
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionRequest {
+    pub network_config: NetworkConfig,
+    pub transaction: String,
+}
+
+/// Example response
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "success": true,
+///     "err": null,
+///     "logs": ["Program QMNeHCGYnLVDn1icRAfQZpjPLBNkfGbSKRB83G5d8KB invoke [1]", ...],
+///     "miners": [...], ~ see AccountDiff
+///     "quarries": [...], ~ see AccountDiff
+///     "token_balance_changes": [...] ~ see TokenBalanceChange
+/// }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionResponse {
+    pub network_config: NetworkConfig,
+    pub success: bool,
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub miners: Vec<AccountDiff<MinerWrapper>>,
+    pub quarries: Vec<AccountDiff<QuarryWrapper>>,
+    pub token_balance_changes: Vec<TokenBalanceChange>,
+}
+
+/// State of an account before and after the simulation, plus the fields that changed
+#[derive(Serialize, Deserialize)]
+pub struct AccountDiff<T> {
+    pub pubkey: Pubkey,
+    /// None when the account doesn't exist yet, e.g. a miner created by the transaction
+    pub before: Option<T>,
+    pub after: Option<T>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl<T: Serialize> AccountDiff<T> {
+    pub fn new(pubkey: Pubkey, before: Option<T>, after: Option<T>) -> Self {
+        let changes = diff_fields(before.as_ref(), after.as_ref());
+
+        Self {
+            pubkey,
+            before,
+            after,
+            changes,
+        }
+    }
+}
+
+/// Change in the balance of an SPL token account
+#[derive(Serialize, Deserialize)]
+pub struct TokenBalanceChange {
+    pub pubkey: Pubkey,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub before: u64,
+    pub after: u64,
+    pub change: i128,
+}
+
+impl TokenBalanceChange {
+    /// Returns None if neither side is a valid token account or the balance didn't change
+    pub fn compute(pubkey: Pubkey, pre: Option<&Account>, post: Option<&Account>) -> Option<Self> {
+        let unpack = |account: Option<&Account>| {
+            account.and_then(|a| spl_token::state::Account::unpack(&a.data).ok())
+        };
+
+        let pre = unpack(pre);
+        let post = unpack(post);
+        let token_account = post.as_ref().or_else(|| pre.as_ref())?;
+
+        let before = pre.as_ref().map(|a| a.amount).unwrap_or(0);
+        let after = post.as_ref().map(|a| a.amount).unwrap_or(0);
+
+        if before == after {
+            return None;
+        }
+
+        Some(Self {
+            pubkey,
+            mint: token_account.mint,
+            owner: token_account.owner,
+            before,
+            after,
+            change: after as i128 - before as i128,
+        })
+    }
+}
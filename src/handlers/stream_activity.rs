@@ -0,0 +1,160 @@
+use crate::utils::*;
+use axum::extract::{Extension, Query};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::{http::StatusCode, Json};
+use futures::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::Level;
+
+/// Stream decoded quarry_mine activity as Server-Sent Events, optionally filtered by rewarder,
+/// quarry or authority
+pub async fn stream_activity_handler(
+    Query(query): Query<StreamActivityQuery>,
+    Extension(hub): Extension<Arc<ActivityHub>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<serde_json::Value>)>
+{
+    let filter = match ActivityFilter::parse(&query) {
+        Some(value) => value,
+        None => {
+            tracing::event!(Level::ERROR, "Activity filter parse failed - Step 1/2");
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(Value::String(
+                    "rewarder, quarry and authority must be base58 pubkeys".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let with_instructions = query.instructions.unwrap_or(false) || filter.needs_instructions();
+
+    let receiver = match hub
+        .subscribe(Network::fetch_variant(&query.network), with_instructions)
+        .await
+    {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Logs subscription successful - Step 2/2");
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Logs subscription failed - Step 2/2");
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(Value::String(
+                    "Failed to subscribe to program logs".to_string(),
+                )),
+            ));
+        }
+    };
+
+    let events = stream::unfold((receiver, filter), |(mut receiver, filter)| async move {
+        loop {
+            match receiver.receiver.recv().await {
+                Ok(activity) => {
+                    if let Some(activity) = filter.apply(activity) {
+                        let event = Event::default()
+                            .event("activity")
+                            .id(activity.signature.clone())
+                            .data(serde_json::to_string(&activity).unwrap_or_default());
+
+                        return Some((Ok(event), (receiver, filter)));
+                    }
+                }
+                // Slow client, skip ahead to the latest activity
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                // Upstream dropped, ending the stream lets EventSource reconnect
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Example request
+///
+/// GET /activity/stream?network=Mainnet&rewarder=rXhAofQCT7NN9TUqigyEAUzV1uLL4boeD8CRkNBSkYk
+///
+/// network defaults to Mainnet. rewarder, quarry and authority are optional base58 pubkeys and
+/// every one given must match. Matching needs the decoded instructions, as does setting
+/// instructions=true. Otherwise only the events found in the logs are streamed, plus the
+/// instructions of pauses, authority and famine changes and fee extractions, which emit none.
+#[derive(Serialize, Deserialize)]
+pub struct StreamActivityQuery {
+    #[serde(default)]
+    pub network: String,
+    pub rewarder: Option<String>,
+    pub quarry: Option<String>,
+    pub authority: Option<String>,
+    pub instructions: Option<bool>,
+}
+
+/// Which instructions a client wants to see
+pub struct ActivityFilter {
+    pub rewarder: Option<Pubkey>,
+    pub quarry: Option<Pubkey>,
+    pub authority: Option<Pubkey>,
+}
+
+impl ActivityFilter {
+    /// Returns None if any of the given pubkeys isn't valid base58
+    pub fn parse(query: &StreamActivityQuery) -> Option<Self> {
+        let parse = |key: &Option<String>| match key {
+            Some(value) => Pubkey::from_str(value).ok().map(Some),
+            None => Some(None),
+        };
+
+        Some(Self {
+            rewarder: parse(&query.rewarder)?,
+            quarry: parse(&query.quarry)?,
+            authority: parse(&query.authority)?,
+        })
+    }
+
+    /// Whether the filter matches on anything, which takes the transaction's instructions
+    pub fn needs_instructions(&self) -> bool {
+        self.rewarder.is_some() || self.quarry.is_some() || self.authority.is_some()
+    }
+
+    /// Keeps only the instructions matching the filter. Returns None when nothing is left.
+    pub fn apply(&self, mut activity: ProgramActivity) -> Option<ProgramActivity> {
+        if !self.needs_instructions() {
+            return if activity.instructions.is_empty() && activity.events.is_empty() {
+                None
+            } else {
+                Some(activity)
+            };
+        }
+
+        activity.instructions.retain(|ix| self.matches(ix));
+
+        if activity.instructions.is_empty() {
+            None
+        } else {
+            Some(activity)
+        }
+    }
+
+    /// Accounts are matched on the last segment of their name, so "stake.quarry" and
+    /// "auth.rewarder" count as the quarry and rewarder, and "pause_authority" as the authority
+    fn matches(&self, ix: &DecodedInstruction) -> bool {
+        let has = |role: &str, key: &Option<Pubkey>| match key {
+            Some(key) => ix.accounts.iter().any(|a| {
+                let name = a.name.rsplit('.').next().unwrap_or(&a.name);
+                a.pubkey == *key && name.ends_with(role)
+            }),
+            None => true,
+        };
+
+        has("rewarder", &self.rewarder)
+            && has("quarry", &self.quarry)
+            && has("authority", &self.authority)
+    }
+}
@@ -0,0 +1,199 @@
+use crate::utils::*;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::Extension;
+use axum::response::IntoResponse;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::Level;
+
+/// Upgrade to a WebSocket over which clients subscribe to Quarry, Miner and Rewarder accounts
+/// and receive the freshly decoded account whenever it changes
+pub async fn subscribe_accounts_handler(
+    ws: WebSocketUpgrade,
+    Extension(hub): Extension<Arc<SubscriptionHub>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, hub))
+}
+
+async fn handle_socket(socket: WebSocket, hub: Arc<SubscriptionHub>) {
+    let (mut sink, mut stream) = socket.split();
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<SubscriptionMessage>();
+
+    // Single writer so every subscription can push to the client independently
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let text = match serde_json::to_string(&message) {
+                Ok(value) => value,
+                Err(_e) => continue,
+            };
+
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut forwarders: HashMap<(Network, Pubkey), JoinHandle<()>> = HashMap::new();
+
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            Message::Text(value) => value,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let request: SubscriptionRequest = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(e) => {
+                let _ = outgoing.send(SubscriptionMessage::Error(e.to_string()));
+                continue;
+            }
+        };
+
+        let network = request.network_config.variant;
+        let pubkey = request.pubkey_config.pubkey;
+        let key = (network, pubkey);
+
+        match request.action {
+            SubscriptionAction::Subscribe => {
+                if forwarders.contains_key(&key) {
+                    continue;
+                }
+
+                let mut receiver = match hub.subscribe(network, pubkey).await {
+                    Ok(value) => {
+                        tracing::event!(Level::INFO, "Account subscription successful");
+                        value
+                    }
+
+                    Err(SubscribeError::TooManyTopics) => {
+                        tracing::event!(Level::ERROR, "Account subscription limit reached");
+                        let _ = outgoing.send(SubscriptionMessage::Error(
+                            "Too many accounts are being watched, try again later".to_string(),
+                        ));
+                        continue;
+                    }
+
+                    Err(SubscribeError::Pubsub(_e)) => {
+                        tracing::event!(Level::ERROR, "Account subscription failed");
+                        let _ = outgoing.send(SubscriptionMessage::Error(
+                            "Failed to subscribe to account".to_string(),
+                        ));
+                        continue;
+                    }
+                };
+
+                let updates = outgoing.clone();
+                forwarders.insert(
+                    key,
+                    tokio::spawn(async move {
+                        loop {
+                            match receiver.recv().await {
+                                Ok(update) => {
+                                    if updates.send(SubscriptionMessage::Update(update)).is_err() {
+                                        break;
+                                    }
+                                }
+                                // Slow client, skip ahead to the latest updates
+                                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                                Err(broadcast::error::RecvError::Closed) => {
+                                    let _ = updates.send(SubscriptionMessage::Closed(
+                                        SubscriptionStatus { network, pubkey },
+                                    ));
+                                    break;
+                                }
+                            }
+                        }
+                    }),
+                );
+
+                let _ = outgoing.send(SubscriptionMessage::Subscribed(SubscriptionStatus {
+                    network,
+                    pubkey,
+                }));
+            }
+
+            SubscriptionAction::Unsubscribe => {
+                if let Some(forwarder) = forwarders.remove(&key) {
+                    forwarder.abort();
+                }
+
+                let _ = outgoing.send(SubscriptionMessage::Unsubscribed(SubscriptionStatus {
+                    network,
+                    pubkey,
+                }));
+            }
+        }
+    }
+
+    // Dropping the receivers lets the hub close upstream subscriptions nobody else watches
+    for (_, forwarder) in forwarders.drain() {
+        forwarder.abort();
+    }
+
+    writer.abort();
+}
+
+/// Whether a client message opens or closes a subscription
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SubscriptionAction {
+    Subscribe,
+    Unsubscribe,
+}
+
+/// Example client message
+///
+/// {
+///     "action": "Subscribe", ~ or "Unsubscribe"
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ a quarry, miner or rewarder
+///     }
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct SubscriptionRequest {
+    pub action: SubscriptionAction,
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+}
+
+/// Example server messages
+///
+/// { "Subscribed": { "network": "Mainnet", "pubkey": [...] } }
+///
+/// {
+///     "Update": {
+///         "network": "Mainnet",
+///         "pubkey": [...],
+///         "slot": 108000000,
+///         "account": { "Quarry": {...} } ~ or "Miner" / "Rewarder", null if closed
+///     }
+/// }
+///
+/// { "Unsubscribed": { "network": "Mainnet", "pubkey": [...] } }
+/// { "Closed": { "network": "Mainnet", "pubkey": [...] } } ~ upstream dropped, resubscribe
+/// { "Error": "Failed to subscribe to account" }
+///
+/// NOTE: All pubkeys will be represented as an array of 32 unsigned 8-bit integers
+#[derive(Serialize, Deserialize)]
+pub enum SubscriptionMessage {
+    Subscribed(SubscriptionStatus),
+    Update(AccountUpdate),
+    Unsubscribed(SubscriptionStatus),
+    Closed(SubscriptionStatus),
+    Error(String),
+}
+
+/// The account a status message refers to
+#[derive(Serialize, Deserialize)]
+pub struct SubscriptionStatus {
+    pub network: Network,
+    pub pubkey: Pubkey,
+}
@@ -3,10 +3,20 @@ mod utils;
 use handlers::*;
 use tracing::Level;
 
-use axum::{body::Body, routing::get, Router};
+use axum::{body::Body, routing::get, AddExtensionLayer, Router};
 use dotenv::dotenv;
 use std::env;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use utils::{
+    run_health_checks, run_indexer, watch_webhooks, AccountCache, ActivityHub, CacheConfig,
+    Network, PriceSource, QuarryIndex, SubscriptionHub, WebhookStore, DEFAULT_CACHE_MAX_BYTES,
+    DEFAULT_HEALTH_CHECK_SECONDS, DEFAULT_INDEXER_INTERVAL_SECONDS, DEFAULT_MINER_TTL_SECONDS,
+    DEFAULT_QUARRY_TTL_SECONDS, DEFAULT_REWARDER_TTL_SECONDS, DEFAULT_WEBHOOK_POLL_SECONDS,
+    DEFAULT_WEBHOOK_STORE_PATH,
+};
 
 #[tokio::main]
 async fn main() {
@@ -16,14 +26,134 @@ async fn main() {
     // .env var checks
     let port_env = env::var("PORT").expect("PORT must be set");
     let port = port_env.parse::<u16>().unwrap();
+    let webhook_store_path =
+        env::var("WEBHOOK_STORE_PATH").unwrap_or_else(|_| DEFAULT_WEBHOOK_STORE_PATH.to_string());
+    let webhook_poll_seconds = env::var("WEBHOOK_POLL_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_WEBHOOK_POLL_SECONDS);
+
+    // Health check the RPC endpoints of every network requests are expected for
+    let health_check_networks: Vec<Network> = env::var("RPC_HEALTH_CHECK_NETWORKS")
+        .unwrap_or_else(|_| "Mainnet,Devnet".to_string())
+        .split(',')
+        .map(|s| Network::fetch_variant(s.trim()))
+        .collect();
+    let health_check_seconds = env::var("RPC_HEALTH_CHECK_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_HEALTH_CHECK_SECONDS);
+    tokio::spawn(run_health_checks(
+        health_check_networks,
+        Duration::from_secs(health_check_seconds),
+    ));
+
+    // Start watching registered webhooks in the background
+    let webhook_store = Arc::new(
+        WebhookStore::load(PathBuf::from(webhook_store_path))
+            .expect("WEBHOOK_STORE_PATH must be a readable webhook store"),
+    );
+    tokio::spawn(watch_webhooks(
+        webhook_store.clone(),
+        Duration::from_secs(webhook_poll_seconds),
+    ));
+
+    // Indexer mode is only enabled when INDEXER_DB_PATH is set
+    let quarry_index = env::var("INDEXER_DB_PATH").ok().map(|path| {
+        Arc::new(QuarryIndex::open(path).expect("INDEXER_DB_PATH must be a writable SQLite file"))
+    });
+
+    if let Some(index) = quarry_index.clone() {
+        let networks: Vec<Network> = env::var("INDEXER_NETWORKS")
+            .unwrap_or_else(|_| "Mainnet".to_string())
+            .split(',')
+            .map(|s| Network::fetch_variant(s.trim()))
+            .collect();
+        let interval = env::var("INDEXER_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_INDEXER_INTERVAL_SECONDS);
+
+        tracing::event!(Level::INFO, "Indexer enabled for {:?}", networks);
+        tokio::spawn(run_indexer(index, networks, Duration::from_secs(interval)));
+    }
+
+    // APR history is only available when PRICE_HISTORY_PATH is set
+    let price_source = env::var("PRICE_HISTORY_PATH").ok().map(|path| {
+        Arc::new(PriceSource {
+            path: PathBuf::from(path),
+        })
+    });
+
+    // Account cache for /quarry, /miner and /rewarder
+    let ttl = |key: &str, default: u64| {
+        Duration::from_secs(
+            env::var(key)
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or(default),
+        )
+    };
+    let account_cache = Arc::new(AccountCache::new(CacheConfig {
+        quarry_ttl: ttl("CACHE_QUARRY_TTL_SECONDS", DEFAULT_QUARRY_TTL_SECONDS),
+        miner_ttl: ttl("CACHE_MINER_TTL_SECONDS", DEFAULT_MINER_TTL_SECONDS),
+        rewarder_ttl: ttl("CACHE_REWARDER_TTL_SECONDS", DEFAULT_REWARDER_TTL_SECONDS),
+        max_bytes: env::var("CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_CACHE_MAX_BYTES),
+    }));
 
     // Declare API router and routes
     let app: Router<Body> = Router::new()
         .route("/", get(root))
         .route("/blockheight", get(fetch_blockheight_handler))
+        .route("/rpc/health", get(fetch_rpc_health_handler))
         .route("/quarry", get(fetch_quarry_handler))
+        .route("/quarry/miners", get(fetch_miners_for_quarry_handler))
+        .route("/quarry/leaderboard", get(fetch_miner_leaderboard_handler))
         .route("/miner", get(fetch_miner_handler))
-        .route("/rewarder", get(fetch_rewarder_handler));
+        .route("/miner/rewards", get(fetch_miner_rewards_handler))
+        .route("/rewarder", get(fetch_rewarder_handler))
+        .route("/rewarder/summary", get(fetch_rewarder_summary_handler))
+        .route("/rewarder/liability", get(fetch_rewarder_liability_handler))
+        .route("/snapshot", get(fetch_snapshot_handler))
+        .route("/accounts", get(fetch_filtered_accounts_handler))
+        .route("/query", get(query_accounts_handler))
+        .route("/timeseries/quarry", get(fetch_quarry_timeseries_handler))
+        .route(
+            "/timeseries/rewarder",
+            get(fetch_rewarder_timeseries_handler),
+        )
+        .route("/timeseries/apr", get(fetch_apr_history_handler))
+        .route("/timeseries/positions", get(fetch_position_history_handler))
+        .route("/pda/quarry", get(derive_quarry_handler))
+        .route("/pda/miner", get(derive_miner_handler))
+        .route("/pda/rewarder", get(derive_rewarder_handler))
+        .route("/pda/miner_vault", get(derive_miner_vault_handler))
+        .route("/tx/create_miner", get(build_create_miner_handler))
+        .route("/tx/stake", get(build_stake_handler))
+        .route("/tx/withdraw", get(build_withdraw_handler))
+        .route("/tx/claim", get(build_claim_handler))
+        .route("/tx/admin", get(build_admin_transaction_handler))
+        .route("/tx/simulate", get(simulate_transaction_handler))
+        .route("/tx/decode", get(decode_transaction_handler))
+        .route("/activity", get(fetch_activity_handler))
+        .route("/activity/stream", get(stream_activity_handler))
+        .route("/claims/export", get(export_claims_handler))
+        .route("/ws", get(subscribe_accounts_handler))
+        .route(
+            "/webhooks",
+            get(list_webhooks_handler)
+                .post(register_webhook_handler)
+                .delete(delete_webhook_handler),
+        )
+        .layer(AddExtensionLayer::new(Arc::new(SubscriptionHub::default())))
+        .layer(AddExtensionLayer::new(Arc::new(ActivityHub::default())))
+        .layer(AddExtensionLayer::new(webhook_store))
+        .layer(AddExtensionLayer::new(quarry_index))
+        .layer(AddExtensionLayer::new(price_source))
+        .layer(AddExtensionLayer::new(account_cache));
 
     // Bind server to PORT and serve the router
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_sdk::account::Account;
+
+use crate::{MinerWrapper, QuarryWrapper, RewarderWrapper};
+
+/// Any quarry_mine account, decoded into its wrapper
+#[derive(Clone, Serialize, Deserialize)]
+pub enum QuarryAccount {
+    Quarry(QuarryWrapper),
+    Miner(MinerWrapper),
+    Rewarder(RewarderWrapper),
+}
+
+impl QuarryAccount {
+    /// Decodes a quarry_mine account by trying each wrapper in turn. The anchor discriminator
+    /// check in each wrapper ensures at most one of them succeeds.
+    pub fn decode(account: &Account) -> Option<Self> {
+        if account.owner != quarry_mine::ID {
+            return None;
+        }
+
+        if let Ok(value) = QuarryWrapper::wrap(account) {
+            return Some(Self::Quarry(value));
+        }
+
+        if let Ok(value) = MinerWrapper::wrap(account) {
+            return Some(Self::Miner(value));
+        }
+
+        RewarderWrapper::wrap(account).ok().map(Self::Rewarder)
+    }
+
+    /// The wrapper as a JSON object, without the variant tag
+    pub fn to_value(&self) -> Value {
+        let value = match self {
+            Self::Quarry(quarry) => serde_json::to_value(quarry),
+            Self::Miner(miner) => serde_json::to_value(miner),
+            Self::Rewarder(rewarder) => serde_json::to_value(rewarder),
+        };
+
+        value.unwrap_or(Value::Null)
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+
+use crate::utils::*;
+
+/// Kind of position changing Quarry activity
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ActivityKind {
+    Stake,
+    Withdraw,
+    Claim,
+}
+
+/// A single stake, withdrawal or claim found in a transaction
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Index of the instruction within the transaction, see DecodedInstruction::index
+    pub instruction_index: String,
+    pub kind: ActivityKind,
+    pub authority: Option<Pubkey>,
+    pub quarry: Option<Pubkey>,
+    pub miner: Option<Pubkey>,
+    /// Staked token amount for stakes and withdrawals, rewards amount paid to the user for
+    /// claims
+    pub amount: Option<u64>,
+    /// Claim fee taken by the rewarder, claims only
+    pub fees: Option<u64>,
+    /// Rewards token mint, claims only
+    pub rewards_token: Option<Pubkey>,
+}
+
+impl ActivityEntry {
+    /// Extracts every stake, withdrawal and claim from a successful decoded transaction.
+    /// Claim amounts come from the ClaimEvents emitted by quarry_mine. quarry_mine skips the
+    /// event when nothing was earned, so events are matched to claim_rewards instructions by
+    /// authority and staked token rather than by position, and claims without a matching
+    /// event are left without an amount.
+    pub fn from_transaction(tx: &DecodedTransaction) -> Vec<Self> {
+        if !tx.success {
+            return Vec::new();
+        }
+
+        let mut claim_events: Vec<Option<&DecodedEvent>> = tx
+            .events
+            .iter()
+            .filter(|e| e.program_id == quarry_mine::ID && e.name == "ClaimEvent")
+            .map(Some)
+            .collect();
+
+        let mut entries = Vec::new();
+
+        for ix in tx
+            .instructions
+            .iter()
+            .filter(|ix| ix.program_id == quarry_mine::ID)
+        {
+            let (kind, prefix) = match ix.name.as_deref() {
+                Some("stake_tokens") => (ActivityKind::Stake, ""),
+                Some("withdraw_tokens") => (ActivityKind::Withdraw, ""),
+                Some("claim_rewards") => (ActivityKind::Claim, "stake."),
+                _ => continue,
+            };
+
+            let mut entry = Self {
+                signature: tx.signature.clone(),
+                slot: tx.slot,
+                block_time: tx.block_time,
+                instruction_index: ix.index.clone(),
+                kind,
+                authority: ix.account(&format!("{}authority", prefix)),
+                quarry: ix.account(&format!("{}quarry", prefix)),
+                miner: ix.account(&format!("{}miner", prefix)),
+                amount: ix.args.get("amount").and_then(Value::as_u64),
+                fees: None,
+                rewards_token: None,
+            };
+
+            if kind == ActivityKind::Claim {
+                let rewarder = ix.account("stake.rewarder");
+                let matching = claim_events.iter_mut().find(|event| {
+                    event.map_or(false, |event| {
+                        entry.authority.is_some()
+                            && event_pubkey(event, "authority") == entry.authority
+                            && claims_quarry(event, rewarder, entry.quarry)
+                    })
+                });
+
+                if let Some(event) = matching.and_then(Option::take) {
+                    entry.amount = event.data.get("amount").and_then(Value::as_u64);
+                    entry.fees = event.data.get("fees").and_then(Value::as_u64);
+                    entry.rewards_token = event_pubkey(event, "rewards_token");
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        entries
+    }
+
+    /// True when the entry touches the given address as its authority, quarry or miner
+    pub fn involves(&self, address: &Pubkey) -> bool {
+        self.authority.as_ref() == Some(address)
+            || self.quarry.as_ref() == Some(address)
+            || self.miner.as_ref() == Some(address)
+    }
+}
+
+/// Reads a pubkey field of a decoded event
+fn event_pubkey(event: &DecodedEvent, field: &str) -> Option<Pubkey> {
+    event
+        .data
+        .get(field)
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+/// True when the ClaimEvent was emitted for the quarry of the rewarder and the event's staked
+/// token, which is how quarry_mine derives the quarry address
+fn claims_quarry(event: &DecodedEvent, rewarder: Option<Pubkey>, quarry: Option<Pubkey>) -> bool {
+    match (rewarder, quarry, event_pubkey(event, "staked_token")) {
+        (Some(rewarder), Some(quarry), Some(staked_token)) => {
+            find_quarry_address(&rewarder, &staked_token).0 == quarry
+        }
+        _ => false,
+    }
+}
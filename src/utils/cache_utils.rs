@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::ClientError;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::utils::*;
+
+/// Default cache lifetimes, override with CACHE_{QUARRY,MINER,REWARDER}_TTL_SECONDS
+pub const DEFAULT_QUARRY_TTL_SECONDS: u64 = 10;
+pub const DEFAULT_MINER_TTL_SECONDS: u64 = 2;
+pub const DEFAULT_REWARDER_TTL_SECONDS: u64 = 60;
+
+/// Default cap on cached account data, override with CACHE_MAX_BYTES
+pub const DEFAULT_CACHE_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Rough per entry bookkeeping cost counted towards the cap on top of the account data
+const CACHE_ENTRY_OVERHEAD: usize = 256;
+
+/// Kind of account a cache entry holds, which decides how long it stays fresh
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CachedAccountKind {
+    Quarry,
+    Miner,
+    Rewarder,
+}
+
+pub struct CacheConfig {
+    pub quarry_ttl: Duration,
+    pub miner_ttl: Duration,
+    pub rewarder_ttl: Duration,
+    pub max_bytes: usize,
+}
+
+impl CacheConfig {
+    /// A zero TTL disables caching for that kind of account
+    pub fn ttl(&self, kind: CachedAccountKind) -> Duration {
+        match kind {
+            CachedAccountKind::Quarry => self.quarry_ttl,
+            CachedAccountKind::Miner => self.miner_ttl,
+            CachedAccountKind::Rewarder => self.rewarder_ttl,
+        }
+    }
+}
+
+/// Where a response's account data came from
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CacheMetadata {
+    pub hit: bool,
+    /// Slot the account data was read at
+    pub slot: u64,
+    /// Milliseconds since the account data was fetched, 0 on a miss
+    pub age_ms: u64,
+}
+
+type CacheKey = (Network, Pubkey, Commitment);
+
+struct CacheEntry {
+    account: Account,
+    slot: u64,
+    fetched_at: Instant,
+    last_used: u64,
+    size: usize,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    bytes: usize,
+    /// Bumped on every access, used to find the least recently used entry
+    clock: u64,
+}
+
+/// In-memory account cache keyed by (network, pubkey, commitment), with a TTL per account kind
+/// and a cap on total memory. The least recently used entries are evicted once the cap is hit.
+pub struct AccountCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl AccountCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Returns the account from the cache if it is still fresh, fetching it otherwise
+    pub fn fetch(
+        &self,
+        network: Network,
+        pubkey: &Pubkey,
+        commitment: Commitment,
+        kind: CachedAccountKind,
+    ) -> Result<(Account, CacheMetadata), ClientError> {
+        let key = (network, *pubkey, commitment);
+        let ttl = self.config.ttl(kind);
+
+        if let Some(hit) = self.lookup(&key, ttl) {
+            return Ok(hit);
+        }
+
+        // The lock isn't held while fetching so a slow RPC call doesn't stall every reader
+        let (account, slot) = fetch_account_with_slot(&network, pubkey, commitment)?;
+
+        if !ttl.is_zero() {
+            self.store(key, account.clone(), slot);
+        }
+
+        Ok((
+            account,
+            CacheMetadata {
+                hit: false,
+                slot,
+                age_ms: 0,
+            },
+        ))
+    }
+
+    fn lookup(&self, key: &CacheKey, ttl: Duration) -> Option<(Account, CacheMetadata)> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        let entry = state.entries.get_mut(key)?;
+        let age = entry.fetched_at.elapsed();
+
+        if age >= ttl {
+            return None;
+        }
+
+        entry.last_used = clock;
+
+        Some((
+            entry.account.clone(),
+            CacheMetadata {
+                hit: true,
+                slot: entry.slot,
+                age_ms: age.as_millis() as u64,
+            },
+        ))
+    }
+
+    fn store(&self, key: CacheKey, account: Account, slot: u64) {
+        let size = account.data.len() + CACHE_ENTRY_OVERHEAD;
+        if size > self.config.max_bytes {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+
+        // A concurrent fetch may have hit a node further ahead, never go back in slots
+        if let Some(existing) = state.entries.get_mut(&key) {
+            if existing.slot > slot {
+                existing.fetched_at = Instant::now();
+                existing.last_used = clock;
+                return;
+            }
+        }
+
+        if let Some(previous) = state.entries.remove(&key) {
+            state.bytes -= previous.size;
+        }
+
+        while state.bytes + size > self.config.max_bytes {
+            let oldest = match state.entries.iter().min_by_key(|(_, e)| e.last_used) {
+                Some((k, _)) => *k,
+                None => break,
+            };
+
+            if let Some(evicted) = state.entries.remove(&oldest) {
+                state.bytes -= evicted.size;
+            }
+        }
+
+        state.bytes += size;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                account,
+                slot,
+                fetched_at: Instant::now(),
+                last_used: clock,
+                size,
+            },
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use crate::utils::Network;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+
+/// Identifies an upstream RPC call, identical calls share a key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RpcCallKey {
+    Account {
+        network: Network,
+        pubkey: String,
+        commitment: String,
+    },
+    ProgramAccounts {
+        network: Network,
+        program_id: String,
+        filters: String,
+        commitment: String,
+        data_slice: String,
+    },
+}
+
+/// A call in flight. Followers wait on `done` until the leader stores the result.
+struct Flight<T> {
+    result: Mutex<Option<Result<T, String>>>,
+    done: Condvar,
+}
+
+/// Single-flight coalescing of blocking calls: while a call for a key is in flight, identical
+/// calls wait for it and share its result instead of issuing their own.
+pub struct SingleFlight<K, T> {
+    flights: Mutex<HashMap<K, Arc<Flight<T>>>>,
+}
+
+impl<K: Clone + Eq + Hash, T: Clone> SingleFlight<K, T> {
+    pub fn new() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs `call` unless an identical call is already in flight. ClientError can't be cloned,
+    /// so followers of a failed call get a Custom error carrying the leader's message.
+    pub fn run<F>(&self, key: K, call: F) -> Result<T, ClientError>
+    where
+        F: FnOnce() -> Result<T, ClientError>,
+    {
+        let (flight, leader) = {
+            let mut flights = self.flights.lock().unwrap();
+
+            match flights.get(&key) {
+                Some(flight) => (flight.clone(), false),
+                None => {
+                    let flight = Arc::new(Flight {
+                        result: Mutex::new(None),
+                        done: Condvar::new(),
+                    });
+                    flights.insert(key.clone(), flight.clone());
+                    (flight, true)
+                }
+            }
+        };
+
+        if !leader {
+            let mut result = flight.result.lock().unwrap();
+            while result.is_none() {
+                result = flight.done.wait(result).unwrap();
+            }
+
+            return match result.as_ref().unwrap() {
+                Ok(value) => Ok(value.clone()),
+                Err(message) => Err(ClientErrorKind::Custom(message.clone()).into()),
+            };
+        }
+
+        let mut landing = Landing {
+            flights: &self.flights,
+            key,
+            flight,
+            result: None,
+        };
+
+        let outcome = call();
+
+        landing.result = Some(match &outcome {
+            Ok(value) => Ok(value.clone()),
+            Err(e) => Err(e.to_string()),
+        });
+        drop(landing);
+
+        outcome
+    }
+}
+
+/// Ends the leader's flight when dropped: the key is removed so later callers start a fresh
+/// flight, and followers are handed the result. Dropping it also runs while a panicking call
+/// unwinds, in which case followers get an error instead of waiting forever.
+struct Landing<'a, K: Eq + Hash, T> {
+    flights: &'a Mutex<HashMap<K, Arc<Flight<T>>>>,
+    key: K,
+    flight: Arc<Flight<T>>,
+    result: Option<Result<T, String>>,
+}
+
+impl<'a, K: Eq + Hash, T> Drop for Landing<'a, K, T> {
+    fn drop(&mut self) {
+        let result = self
+            .result
+            .take()
+            .unwrap_or_else(|| Err("Coalesced call panicked".to_string()));
+
+        self.flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.key);
+
+        *self
+            .flight
+            .result
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(result);
+        self.flight.done.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn followers_share_the_leaders_result() {
+        let flights: Arc<SingleFlight<u8, u64>> = Arc::new(SingleFlight::new());
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+
+        let leader = {
+            let flights = flights.clone();
+            thread::spawn(move || {
+                flights.run(1, || {
+                    started.send(()).unwrap();
+                    wait_release.recv().unwrap();
+                    Ok(7)
+                })
+            })
+        };
+
+        wait_started.recv().unwrap();
+        let follower = {
+            let flights = flights.clone();
+            thread::spawn(move || flights.run(1, || Ok(8)))
+        };
+
+        thread::sleep(Duration::from_millis(100));
+        release.send(()).unwrap();
+
+        assert_eq!(leader.join().unwrap().unwrap(), 7);
+        assert_eq!(follower.join().unwrap().unwrap(), 7);
+    }
+
+    #[test]
+    fn leader_panic_releases_followers_and_the_key() {
+        let flights: Arc<SingleFlight<u8, u64>> = Arc::new(SingleFlight::new());
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+
+        let leader = {
+            let flights = flights.clone();
+            thread::spawn(move || {
+                flights.run(1, || {
+                    started.send(()).unwrap();
+                    wait_release.recv().unwrap();
+                    panic!("upstream call panicked");
+                })
+            })
+        };
+
+        wait_started.recv().unwrap();
+        let follower = {
+            let flights = flights.clone();
+            thread::spawn(move || flights.run(1, || Ok(8)))
+        };
+
+        thread::sleep(Duration::from_millis(100));
+        release.send(()).unwrap();
+
+        assert!(leader.join().is_err());
+        assert!(follower.join().unwrap().is_err());
+        assert_eq!(flights.run(1, || Ok(9)).unwrap(), 9);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::utils::*;
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::{from_account, Account};
+use solana_sdk::clock::Clock;
+use solana_sdk::sysvar;
+use tracing::Level;
+
+/// getMultipleAccounts accepts at most 100 keys, one of which is taken by the Clock sysvar.
+/// Larger reads are split into batches of this size.
+pub const MAX_CONSISTENT_ACCOUNTS: usize = 99;
+
+/// Times a batched read is retried to get every batch served at the same slot before it
+/// settles for a slot range
+pub const CONSISTENT_READ_ATTEMPTS: usize = 3;
+
+/// Slot and cluster time a derived value was computed at
+///
+/// {
+///     "slot": 108000000,
+///     "unix_timestamp": 1637000000, ~ Clock sysvar unix_timestamp at that slot
+///     "max_slot": null ~ set when the accounts were read across several slots, see below
+/// }
+///
+/// Reads of more than MAX_CONSISTENT_ACCOUNTS accounts take several requests. If those can't
+/// be served at a single slot, slot and unix_timestamp are the earliest batch's and max_slot
+/// is the slot of the latest one.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ReadContext {
+    pub slot: u64,
+    pub unix_timestamp: i64,
+    pub max_slot: Option<u64>,
+}
+
+/// Accounts read together so they all reflect the same slot, see ReadContext for reads too
+/// large for one request. Accounts are in the order they were requested, None when they don't
+/// exist.
+pub struct ConsistentRead {
+    pub context: ReadContext,
+    pub accounts: Vec<Option<Account>>,
+}
+
+/// Fetches the requested accounts and the Clock sysvar, in one request when they fit. Derived
+/// values computed from the result use `context.unix_timestamp` rather than the local wall
+/// clock, so they match what the program would compute at that slot.
+pub fn fetch_consistent_accounts(
+    network: &Network,
+    account_pubkeys: &[Pubkey],
+    commitment: Commitment,
+) -> Result<ConsistentRead, ClientError> {
+    let mut batches: Vec<&[Pubkey]> = account_pubkeys.chunks(MAX_CONSISTENT_ACCOUNTS).collect();
+    if batches.is_empty() {
+        batches.push(&[]);
+    }
+
+    let mut reads: Vec<(ReadContext, Vec<Option<Account>>)> = Vec::new();
+
+    for _attempt in 0..CONSISTENT_READ_ATTEMPTS {
+        reads = batches
+            .iter()
+            .map(|batch| fetch_batch(network, batch, commitment))
+            .collect::<Result<_, _>>()?;
+
+        if reads
+            .iter()
+            .all(|(context, _)| context.slot == reads[0].0.slot)
+        {
+            break;
+        }
+    }
+
+    let max_slot = reads.iter().map(|(context, _)| context.slot).max();
+    let mut context = reads
+        .iter()
+        .map(|(context, _)| *context)
+        .min_by_key(|context| context.slot)
+        .expect("At least one batch is read");
+
+    if max_slot != Some(context.slot) {
+        tracing::event!(
+            Level::WARN,
+            "Consistent read spans slots {} to {:?}",
+            context.slot,
+            max_slot
+        );
+        context.max_slot = max_slot;
+    }
+
+    Ok(ConsistentRead {
+        context,
+        accounts: reads
+            .into_iter()
+            .flat_map(|(_, accounts)| accounts)
+            .collect(),
+    })
+}
+
+/// Reads up to MAX_CONSISTENT_ACCOUNTS accounts and the Clock sysvar in one request
+fn fetch_batch(
+    network: &Network,
+    account_pubkeys: &[Pubkey],
+    commitment: Commitment,
+) -> Result<(ReadContext, Vec<Option<Account>>), ClientError> {
+    let mut keys = account_pubkeys.to_vec();
+    keys.push(sysvar::clock::ID);
+
+    let (mut accounts, slot) = fetch_multiple_accounts_with_slot(network, &keys, commitment)?;
+
+    let clock = accounts
+        .pop()
+        .flatten()
+        .and_then(|account| from_account::<Clock, _>(&account))
+        .ok_or_else(|| {
+            ClientError::from(ClientErrorKind::Custom("ClockUnavailable".to_string()))
+        })?;
+
+    Ok((
+        ReadContext {
+            slot,
+            unix_timestamp: clock.unix_timestamp,
+            max_slot: None,
+        },
+        accounts,
+    ))
+}
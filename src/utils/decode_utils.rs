@@ -0,0 +1,805 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use solana_program::hash::hash;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    EncodedConfirmedTransaction, UiInstruction, UiTransactionStatusMeta,
+};
+use std::str::FromStr;
+
+use crate::utils::*;
+
+/// Program id of quarry_merge_mine, which isn't a dependency of warlock
+pub const QUARRY_MERGE_MINE_PROGRAM_ID: &str = "QMMD16kjauP5knBwxNUJRZ1Z5o3deBuFrqVjBVmmqto";
+
+/// Borsh types used by Quarry instruction args and event fields
+#[derive(Clone, Copy, Debug)]
+pub enum ArgType {
+    U8,
+    U64,
+    I64,
+    Pubkey,
+}
+
+/// Name, account names and args of an anchor instruction
+pub struct InstructionLayout {
+    pub name: &'static str,
+    /// Accounts past the end of the list, such as remaining accounts, are numbered
+    pub accounts: &'static [&'static str],
+    pub args: &'static [(&'static str, ArgType)],
+}
+
+/// Name and fields of an anchor event
+pub struct EventLayout {
+    pub name: &'static str,
+    pub fields: &'static [(&'static str, ArgType)],
+}
+
+/// Everything needed to decode the instructions and events of one program
+pub struct ProgramLayout {
+    pub name: &'static str,
+    pub program_id: Pubkey,
+    pub instructions: &'static [InstructionLayout],
+    pub events: &'static [EventLayout],
+}
+
+const USER_STAKE: &[&str] = &[
+    "authority",
+    "miner",
+    "quarry",
+    "miner_vault",
+    "token_account",
+    "token_program",
+    "rewarder",
+];
+
+const QUARRY_MINE_INSTRUCTIONS: &[InstructionLayout] = &[
+    InstructionLayout {
+        name: "new_rewarder",
+        accounts: &[
+            "base",
+            "rewarder",
+            "initial_authority",
+            "payer",
+            "system_program",
+            "clock",
+            "mint_wrapper",
+            "rewards_token_mint",
+            "claim_fee_token_account",
+        ],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "set_pause_authority",
+        accounts: &["auth.authority", "auth.rewarder", "new_pause_authority"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "pause",
+        accounts: &["pause_authority", "rewarder"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "unpause",
+        accounts: &["pause_authority", "rewarder"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "transfer_authority",
+        accounts: &["authority", "rewarder"],
+        args: &[("new_authority", ArgType::Pubkey)],
+    },
+    InstructionLayout {
+        name: "accept_authority",
+        accounts: &["authority", "rewarder"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "set_annual_rewards",
+        accounts: &["auth.authority", "auth.rewarder"],
+        args: &[("new_rate", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "create_quarry",
+        accounts: &[
+            "quarry",
+            "auth.authority",
+            "auth.rewarder",
+            "token_mint",
+            "payer",
+            "clock",
+            "system_program",
+        ],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "set_rewards_share",
+        accounts: &["auth.authority", "auth.rewarder", "quarry"],
+        args: &[("new_share", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "set_famine",
+        accounts: &["auth.authority", "auth.rewarder", "quarry"],
+        args: &[("famine_ts", ArgType::I64)],
+    },
+    InstructionLayout {
+        name: "update_quarry_rewards",
+        accounts: &["quarry", "rewarder"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "create_miner",
+        accounts: &[
+            "authority",
+            "miner",
+            "quarry",
+            "rewarder",
+            "system_program",
+            "payer",
+            "token_mint",
+            "miner_vault",
+            "token_program",
+        ],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "claim_rewards",
+        accounts: &[
+            "mint_wrapper",
+            "mint_wrapper_program",
+            "minter",
+            "rewards_token_mint",
+            "rewards_token_account",
+            "claim_fee_token_account",
+            "stake.authority",
+            "stake.miner",
+            "stake.quarry",
+            "stake.miner_vault",
+            "stake.token_account",
+            "stake.token_program",
+            "stake.rewarder",
+        ],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "stake_tokens",
+        accounts: USER_STAKE,
+        args: &[("amount", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "withdraw_tokens",
+        accounts: USER_STAKE,
+        args: &[("amount", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "extract_fees",
+        accounts: &[
+            "rewarder",
+            "claim_fee_token_account",
+            "fee_to_token_account",
+            "token_program",
+        ],
+        args: &[],
+    },
+];
+
+const QUARRY_MINE_EVENTS: &[EventLayout] = &[
+    EventLayout {
+        name: "NewRewarderEvent",
+        fields: &[("authority", ArgType::Pubkey), ("timestamp", ArgType::I64)],
+    },
+    EventLayout {
+        name: "ClaimEvent",
+        fields: &[
+            ("authority", ArgType::Pubkey),
+            ("staked_token", ArgType::Pubkey),
+            ("rewards_token", ArgType::Pubkey),
+            ("amount", ArgType::U64),
+            ("fees", ArgType::U64),
+            ("timestamp", ArgType::I64),
+        ],
+    },
+    EventLayout {
+        name: "StakeEvent",
+        fields: &[
+            ("authority", ArgType::Pubkey),
+            ("token", ArgType::Pubkey),
+            ("amount", ArgType::U64),
+            ("timestamp", ArgType::I64),
+        ],
+    },
+    EventLayout {
+        name: "WithdrawEvent",
+        fields: &[
+            ("authority", ArgType::Pubkey),
+            ("token", ArgType::Pubkey),
+            ("amount", ArgType::U64),
+            ("timestamp", ArgType::I64),
+        ],
+    },
+    EventLayout {
+        name: "RewarderAnnualRewardsUpdateEvent",
+        fields: &[
+            ("previous_rate", ArgType::U64),
+            ("new_rate", ArgType::U64),
+            ("timestamp", ArgType::I64),
+        ],
+    },
+    EventLayout {
+        name: "MinerCreateEvent",
+        fields: &[
+            ("authority", ArgType::Pubkey),
+            ("quarry", ArgType::Pubkey),
+            ("miner", ArgType::Pubkey),
+        ],
+    },
+    EventLayout {
+        name: "QuarryCreateEvent",
+        fields: &[("token_mint", ArgType::Pubkey), ("timestamp", ArgType::I64)],
+    },
+    EventLayout {
+        name: "QuarryRewardsUpdateEvent",
+        fields: &[
+            ("token_mint", ArgType::Pubkey),
+            ("annual_rewards_rate", ArgType::U64),
+            ("rewards_share", ArgType::U64),
+            ("timestamp", ArgType::I64),
+        ],
+    },
+];
+
+const MINT_WRAPPER_INSTRUCTIONS: &[InstructionLayout] = &[
+    InstructionLayout {
+        name: "new_wrapper",
+        accounts: &[
+            "base",
+            "mint_wrapper",
+            "admin",
+            "token_mint",
+            "token_program",
+            "payer",
+            "system_program",
+        ],
+        args: &[("bump", ArgType::U8), ("hard_cap", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "transfer_admin",
+        accounts: &["mint_wrapper", "admin", "next_admin"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "accept_admin",
+        accounts: &["mint_wrapper", "pending_admin"],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "new_minter",
+        accounts: &[
+            "auth.mint_wrapper",
+            "auth.admin",
+            "minter_authority",
+            "minter",
+            "payer",
+            "system_program",
+        ],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "minter_update",
+        accounts: &["auth.mint_wrapper", "auth.admin", "minter"],
+        args: &[("allowance", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "perform_mint",
+        accounts: &[
+            "mint_wrapper",
+            "minter_authority",
+            "token_mint",
+            "destination",
+            "minter",
+            "token_program",
+        ],
+        args: &[("amount", ArgType::U64)],
+    },
+];
+
+const MINT_WRAPPER_EVENTS: &[EventLayout] = &[
+    EventLayout {
+        name: "NewMintWrapperEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("hard_cap", ArgType::U64),
+            ("admin", ArgType::Pubkey),
+            ("token_mint", ArgType::Pubkey),
+        ],
+    },
+    EventLayout {
+        name: "MinterAllowanceUpdateEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("minter", ArgType::Pubkey),
+            ("previous_allowance", ArgType::U64),
+            ("allowance", ArgType::U64),
+        ],
+    },
+    EventLayout {
+        name: "MintWrapperAdminProposeEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("current_admin", ArgType::Pubkey),
+            ("pending_admin", ArgType::Pubkey),
+        ],
+    },
+    EventLayout {
+        name: "MintWrapperAdminUpdateEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("previous_admin", ArgType::Pubkey),
+            ("admin", ArgType::Pubkey),
+        ],
+    },
+    EventLayout {
+        name: "NewMinterEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("minter", ArgType::Pubkey),
+            ("index", ArgType::U64),
+            ("minter_authority", ArgType::Pubkey),
+        ],
+    },
+    EventLayout {
+        name: "MinterMintEvent",
+        fields: &[
+            ("mint_wrapper", ArgType::Pubkey),
+            ("minter", ArgType::Pubkey),
+            ("amount", ArgType::U64),
+            ("destination", ArgType::Pubkey),
+        ],
+    },
+];
+
+// Nested account structs are flattened with their field name as prefix, e.g. the QuarryStake
+// accounts shared by the stake, unstake and claim instructions appear as "stake.<account>"
+const MERGE_MINE_INSTRUCTIONS: &[InstructionLayout] = &[
+    InstructionLayout {
+        name: "new_pool",
+        accounts: &[
+            "pool",
+            "primary_mint",
+            "replica_mint",
+            "payer",
+            "token_program",
+            "system_program",
+            "rent",
+        ],
+        args: &[("bump", ArgType::U8), ("mint_bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "init_merge_miner",
+        accounts: &["pool", "owner", "mm", "payer", "system_program"],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "init_miner",
+        accounts: &[
+            "pool",
+            "mm",
+            "miner",
+            "quarry",
+            "rewarder",
+            "token_mint",
+            "miner_vault",
+            "payer",
+            "mine_program",
+            "system_program",
+            "token_program",
+            "rent",
+        ],
+        args: &[("bump", ArgType::U8)],
+    },
+    InstructionLayout {
+        name: "stake_primary_miner",
+        accounts: &[
+            "mm_owner",
+            "mm_primary_token_account",
+            "stake.pool",
+            "stake.mm",
+            "stake.rewarder",
+            "stake.quarry",
+            "stake.miner",
+            "stake.miner_vault",
+            "stake.token_program",
+            "stake.mine_program",
+            "stake.clock",
+        ],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "stake_replica_miner",
+        accounts: &[
+            "mm_owner",
+            "replica_mint",
+            "replica_mint_token_account",
+            "stake.pool",
+            "stake.mm",
+            "stake.rewarder",
+            "stake.quarry",
+            "stake.miner",
+            "stake.miner_vault",
+            "stake.token_program",
+            "stake.mine_program",
+            "stake.clock",
+        ],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "unstake_primary_miner",
+        accounts: &[
+            "mm_owner",
+            "mm_primary_token_account",
+            "stake.pool",
+            "stake.mm",
+            "stake.rewarder",
+            "stake.quarry",
+            "stake.miner",
+            "stake.miner_vault",
+            "stake.token_program",
+            "stake.mine_program",
+            "stake.clock",
+        ],
+        args: &[("amount", ArgType::U64)],
+    },
+    InstructionLayout {
+        name: "unstake_all_replica_miner",
+        accounts: &[
+            "mm_owner",
+            "replica_mint",
+            "replica_mint_token_account",
+            "stake.pool",
+            "stake.mm",
+            "stake.rewarder",
+            "stake.quarry",
+            "stake.miner",
+            "stake.miner_vault",
+            "stake.token_program",
+            "stake.mine_program",
+            "stake.clock",
+        ],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "withdraw_tokens",
+        accounts: &[
+            "owner",
+            "pool",
+            "mm",
+            "withdraw_mint",
+            "mm_token_account",
+            "token_destination",
+            "token_program",
+        ],
+        args: &[],
+    },
+    InstructionLayout {
+        name: "claim_rewards",
+        accounts: &[
+            "mint_wrapper",
+            "mint_wrapper_program",
+            "minter",
+            "rewards_token_mint",
+            "rewards_token_account",
+            "claim_fee_token_account",
+            "stake_token_account",
+            "stake.pool",
+            "stake.mm",
+            "stake.rewarder",
+            "stake.quarry",
+            "stake.miner",
+            "stake.miner_vault",
+            "stake.token_program",
+            "stake.mine_program",
+            "stake.clock",
+        ],
+        args: &[],
+    },
+];
+
+/// Layouts of every Quarry program warlock knows how to decode
+pub fn program_layouts() -> Vec<ProgramLayout> {
+    vec![
+        ProgramLayout {
+            name: "quarry_mine",
+            program_id: quarry_mine::ID,
+            instructions: QUARRY_MINE_INSTRUCTIONS,
+            events: QUARRY_MINE_EVENTS,
+        },
+        ProgramLayout {
+            name: "quarry_mint_wrapper",
+            program_id: quarry_mint_wrapper::ID,
+            instructions: MINT_WRAPPER_INSTRUCTIONS,
+            events: MINT_WRAPPER_EVENTS,
+        },
+        ProgramLayout {
+            name: "quarry_merge_mine",
+            program_id: Pubkey::from_str(QUARRY_MERGE_MINE_PROGRAM_ID).unwrap(),
+            instructions: MERGE_MINE_INSTRUCTIONS,
+            events: &[],
+        },
+    ]
+}
+
+/// Anchor event discriminator: the first 8 bytes of sha256("event:<name>")
+pub fn anchor_event_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("event:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// Reads borsh encoded values off the front of `data` into a JSON object. Returns None if
+/// the data is too short.
+fn decode_fields(mut data: &[u8], fields: &[(&str, ArgType)]) -> Option<Value> {
+    let mut map = Map::new();
+
+    for (name, arg_type) in fields.iter() {
+        let size = match arg_type {
+            ArgType::U8 => 1,
+            ArgType::U64 | ArgType::I64 => 8,
+            ArgType::Pubkey => 32,
+        };
+
+        if data.len() < size {
+            return None;
+        }
+
+        let (bytes, rest) = data.split_at(size);
+        data = rest;
+
+        let value = match arg_type {
+            ArgType::U8 => Value::from(bytes[0]),
+            ArgType::U64 => Value::from(u64::from_le_bytes(bytes.try_into().ok()?)),
+            ArgType::I64 => Value::from(i64::from_le_bytes(bytes.try_into().ok()?)),
+            ArgType::Pubkey => serde_json::to_value(Pubkey::new(bytes)).ok()?,
+        };
+
+        map.insert(name.to_string(), value);
+    }
+
+    Some(Value::Object(map))
+}
+
+/// An account passed to a decoded instruction
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecodedAccount {
+    pub name: String,
+    pub pubkey: Pubkey,
+}
+
+/// A Quarry program instruction decoded into its name, named accounts and args
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecodedInstruction {
+    /// Position in the transaction, "2" for a top level instruction or "2.1" for the second
+    /// instruction invoked by top level instruction 2
+    pub index: String,
+    pub program: String,
+    pub program_id: Pubkey,
+    /// None when the discriminator doesn't match any known instruction
+    pub name: Option<String>,
+    pub accounts: Vec<DecodedAccount>,
+    pub args: Value,
+}
+
+impl DecodedInstruction {
+    /// Looks up an account of the instruction by the name the program gives it
+    pub fn account(&self, name: &str) -> Option<Pubkey> {
+        self.accounts
+            .iter()
+            .find(|a| a.name == name)
+            .map(|a| a.pubkey)
+    }
+}
+
+/// Decodes a single instruction. Returns None when the program isn't a Quarry program.
+pub fn decode_instruction(
+    index: String,
+    program_id: &Pubkey,
+    accounts: &[Pubkey],
+    data: &[u8],
+) -> Option<DecodedInstruction> {
+    let layouts = program_layouts();
+    let program = layouts.iter().find(|p| &p.program_id == program_id)?;
+
+    let layout = program
+        .instructions
+        .iter()
+        .find(|ix| data.len() >= 8 && data[..8] == anchor_sighash(ix.name));
+
+    let accounts = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, pubkey)| DecodedAccount {
+            name: layout
+                .and_then(|l| l.accounts.get(i))
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("account_{}", i)),
+            pubkey: *pubkey,
+        })
+        .collect();
+
+    Some(DecodedInstruction {
+        index,
+        program: program.name.to_string(),
+        program_id: *program_id,
+        name: layout.map(|l| l.name.to_string()),
+        accounts,
+        args: layout
+            .and_then(|l| decode_fields(&data[8..], l.args))
+            .unwrap_or(Value::Null),
+    })
+}
+
+/// An anchor event emitted by a Quarry program
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecodedEvent {
+    pub program: String,
+    pub program_id: Pubkey,
+    pub name: String,
+    pub data: Value,
+}
+
+/// Finds every anchor event in a transaction's logs. Events are emitted as base64 encoded
+/// "Program log: " lines ("Program data: " on newer runtimes), and the program that emitted
+/// them is tracked through the invoke/success lines.
+pub fn decode_events_from_logs(logs: &[String]) -> Vec<DecodedEvent> {
+    let layouts = program_layouts();
+    let mut stack: Vec<Pubkey> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs.iter() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        match parts.as_slice() {
+            ["Program", id, "invoke", _depth] => {
+                if let Ok(program_id) = Pubkey::from_str(id) {
+                    stack.push(program_id);
+                }
+            }
+            ["Program", _id, "success"] | ["Program", _id, "failed:", ..] => {
+                stack.pop();
+            }
+            ["Program", "log:", payload] | ["Program", "data:", payload] => {
+                let program = match stack
+                    .last()
+                    .and_then(|id| layouts.iter().find(|p| &p.program_id == id))
+                {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                let bytes = match base64::decode(payload) {
+                    Ok(value) if value.len() >= 8 => value,
+                    _ => continue,
+                };
+
+                let layout = program
+                    .events
+                    .iter()
+                    .find(|e| bytes[..8] == anchor_event_discriminator(e.name));
+
+                if let Some(layout) = layout {
+                    if let Some(data) = decode_fields(&bytes[8..], layout.fields) {
+                        events.push(DecodedEvent {
+                            program: program.name.to_string(),
+                            program_id: program.program_id,
+                            name: layout.name.to_string(),
+                            data,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    events
+}
+
+/// A confirmed transaction with every Quarry instruction and event decoded
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecodedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub success: bool,
+    pub err: Option<String>,
+    pub instructions: Vec<DecodedInstruction>,
+    pub events: Vec<DecodedEvent>,
+    pub logs: Vec<String>,
+}
+
+impl DecodedTransaction {
+    /// Decodes a transaction fetched with base64 encoding. Returns None if the transaction
+    /// itself can't be decoded.
+    pub fn decode(signature: &Signature, confirmed: &EncodedConfirmedTransaction) -> Option<Self> {
+        let transaction = confirmed.transaction.transaction.decode()?;
+        let message = &transaction.message;
+        let meta: Option<&UiTransactionStatusMeta> = confirmed.transaction.meta.as_ref();
+
+        let accounts_of = |indexes: &[u8]| -> Vec<Pubkey> {
+            indexes
+                .iter()
+                .filter_map(|i| message.account_keys.get(*i as usize).copied())
+                .collect()
+        };
+
+        let mut instructions = Vec::new();
+
+        for (i, ix) in message.instructions.iter().enumerate() {
+            let program_id = match message.account_keys.get(ix.program_id_index as usize) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if let Some(decoded) = decode_instruction(
+                i.to_string(),
+                program_id,
+                &accounts_of(&ix.accounts),
+                &ix.data,
+            ) {
+                instructions.push(decoded);
+            }
+
+            let inner = meta
+                .and_then(|m| m.inner_instructions.as_ref())
+                .and_then(|inner| inner.iter().find(|set| set.index as usize == i));
+
+            for (j, inner_ix) in inner
+                .map(|set| set.instructions.iter())
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                let compiled = match inner_ix {
+                    UiInstruction::Compiled(value) => value,
+                    _ => continue,
+                };
+
+                let program_id = match message.account_keys.get(compiled.program_id_index as usize)
+                {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                let data = match bs58::decode(&compiled.data).into_vec() {
+                    Ok(value) => value,
+                    Err(_e) => continue,
+                };
+
+                if let Some(decoded) = decode_instruction(
+                    format!("{}.{}", i, j),
+                    program_id,
+                    &accounts_of(&compiled.accounts),
+                    &data,
+                ) {
+                    instructions.push(decoded);
+                }
+            }
+        }
+
+        let logs = meta
+            .and_then(|m| m.log_messages.clone())
+            .unwrap_or_default();
+        let err = meta.and_then(|m| m.err.clone());
+
+        Some(Self {
+            signature: signature.to_string(),
+            slot: confirmed.slot,
+            block_time: confirmed.block_time,
+            success: err.is_none(),
+            err: err.map(|e| format!("{:?}", e)),
+            events: decode_events_from_logs(&logs),
+            instructions,
+            logs,
+        })
+    }
+}
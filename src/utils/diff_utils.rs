@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single top level field whose value differs between two versions of an object
+#[derive(Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// Compares two serializable values field by field. Both are expected to serialize to JSON
+/// objects, e.g. the account wrappers. Fields missing on one side are reported as null.
+pub fn diff_fields<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Vec<FieldChange> {
+    let to_map = |value: Option<&T>| match value.map(serde_json::to_value) {
+        Some(Ok(Value::Object(map))) => map,
+        _ => serde_json::Map::new(),
+    };
+
+    let before = to_map(before);
+    let after = to_map(after);
+
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let b = before.get(field).cloned().unwrap_or(Value::Null);
+            let a = after.get(field).cloned().unwrap_or(Value::Null);
+
+            if a == b {
+                None
+            } else {
+                Some(FieldChange {
+                    field: field.clone(),
+                    before: b,
+                    after: a,
+                })
+            }
+        })
+        .collect()
+}
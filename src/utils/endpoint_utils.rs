@@ -0,0 +1,414 @@
+use crate::utils::*;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::pubsub_client::PubsubClientError;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY;
+use solana_client::rpc_request::RpcError;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// Default number of seconds between endpoint health checks
+pub const DEFAULT_HEALTH_CHECK_SECONDS: u64 = 10;
+
+/// Endpoints more than this many slots behind the highest known slot are considered lagging
+pub const MAX_HEALTHY_SLOT_LAG: u64 = 50;
+
+/// Consecutive failures after which an endpoint is taken out of rotation
+pub const CIRCUIT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an endpoint stays out of rotation before it is tried again
+pub const CIRCUIT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// Attempts made for a single RPC call before the last error is returned
+pub const RPC_MAX_ATTEMPTS: usize = 3;
+
+/// Delay before the first retry, doubled after every failed attempt
+pub const RPC_RETRY_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Timeout for the getSlot call health checks make
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Every RPC endpoint warlock knows about, shared by all rpc_utils calls
+pub static ENDPOINT_POOL: Lazy<EndpointPool> = Lazy::new(EndpointPool::default);
+
+/// Health of a single RPC endpoint as reported by /rpc/health
+///
+/// {
+///     "url": "https://api.mainnet-beta.solana.com",
+///     "slot": 108000000, ~ null until the first successful health check
+///     "latency_ms": 120, ~ getSlot round trip of the last health check
+///     "consecutive_failures": 0,
+///     "lagging": false, ~ true when more than 50 slots behind the best endpoint
+///     "circuit_open": false ~ true while the endpoint is out of rotation
+/// }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub slot: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub lagging: bool,
+    pub circuit_open: bool,
+}
+
+struct Endpoint {
+    url: String,
+    slot: Option<u64>,
+    latency: Option<Duration>,
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Endpoint {
+    fn new(url: String) -> Self {
+        Self {
+            url,
+            slot: None,
+            latency: None,
+            consecutive_failures: 0,
+            open_until: None,
+        }
+    }
+
+    fn is_open(&self, now: Instant) -> bool {
+        self.open_until.map_or(false, |until| until > now)
+    }
+
+    fn is_lagging(&self, best_slot: Option<u64>) -> bool {
+        match (self.slot, best_slot) {
+            (Some(slot), Some(best)) => best.saturating_sub(slot) > MAX_HEALTHY_SLOT_LAG,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// RPC endpoints per network along with their health. Endpoints are read from
+/// `<NETWORK>_RPC_URLS` (comma separated) the first time a network is used, falling back to
+/// Network::default_url.
+#[derive(Default)]
+pub struct EndpointPool {
+    networks: Mutex<HashMap<Network, Vec<Endpoint>>>,
+}
+
+impl EndpointPool {
+    fn with_endpoints<T>(&self, network: Network, f: impl FnOnce(&mut Vec<Endpoint>) -> T) -> T {
+        let mut networks = self.networks.lock().unwrap();
+        let endpoints = networks.entry(network).or_insert_with(|| {
+            configured_urls(network)
+                .into_iter()
+                .map(Endpoint::new)
+                .collect()
+        });
+
+        f(endpoints)
+    }
+
+    /// Urls to try in order. Endpoints in rotation come first, ranked by whether they are
+    /// lagging and then by latency. Endpoints with an open circuit are only tried last, so a
+    /// request still goes out when every endpoint is failing.
+    pub fn ranked_urls(&self, network: Network) -> Vec<String> {
+        let now = Instant::now();
+
+        self.with_endpoints(network, |endpoints| {
+            let best_slot = endpoints.iter().filter_map(|e| e.slot).max();
+            let mut ranked: Vec<&Endpoint> = endpoints.iter().collect();
+
+            // Stable sort keeps the configured order between endpoints that haven't been checked
+            ranked.sort_by_key(|e| {
+                (
+                    e.is_open(now),
+                    e.is_lagging(best_slot),
+                    e.latency.unwrap_or(Duration::MAX),
+                )
+            });
+
+            ranked.into_iter().map(|e| e.url.clone()).collect()
+        })
+    }
+
+    /// Pubsub urls to try in order. Urls set in `<NETWORK>_WS_URLS` are used as configured,
+    /// otherwise every ranked rpc url is mapped to the pubsub url next to it.
+    pub fn ranked_ws_urls(&self, network: Network) -> Vec<String> {
+        let configured = configured_urls_from(network, "WS_URLS");
+
+        if configured.is_empty() {
+            self.ranked_urls(network)
+                .iter()
+                .map(|url| pubsub_url(url))
+                .collect()
+        } else {
+            configured
+        }
+    }
+
+    /// Closes the endpoint's circuit and resets its failure count
+    pub fn record_success(&self, network: Network, url: &str) {
+        self.with_endpoints(network, |endpoints| {
+            if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+                endpoint.consecutive_failures = 0;
+                endpoint.open_until = None;
+            }
+        })
+    }
+
+    /// Counts a failure against the endpoint, opening its circuit once the threshold is hit
+    pub fn record_failure(&self, network: Network, url: &str) {
+        self.with_endpoints(network, |endpoints| {
+            if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+                endpoint.consecutive_failures += 1;
+
+                if endpoint.consecutive_failures >= CIRCUIT_FAILURE_THRESHOLD {
+                    if !endpoint.is_open(Instant::now()) {
+                        tracing::event!(
+                            Level::WARN,
+                            "Taking {} out of rotation after {} failures",
+                            url,
+                            endpoint.consecutive_failures
+                        );
+                    }
+                    endpoint.open_until = Some(Instant::now() + CIRCUIT_OPEN_DURATION);
+                }
+            }
+        })
+    }
+
+    fn record_health(&self, network: Network, url: &str, slot: u64, latency: Duration) {
+        self.with_endpoints(network, |endpoints| {
+            if let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) {
+                endpoint.slot = Some(slot);
+                endpoint.latency = Some(latency);
+            }
+        });
+        self.record_success(network, url);
+    }
+
+    /// Current health of every endpoint configured for the network
+    pub fn status(&self, network: Network) -> Vec<EndpointStatus> {
+        let now = Instant::now();
+
+        self.with_endpoints(network, |endpoints| {
+            let best_slot = endpoints.iter().filter_map(|e| e.slot).max();
+
+            endpoints
+                .iter()
+                .map(|e| EndpointStatus {
+                    url: e.url.clone(),
+                    slot: e.slot,
+                    latency_ms: e.latency.map(|l| l.as_millis() as u64),
+                    consecutive_failures: e.consecutive_failures,
+                    lagging: e.is_lagging(best_slot),
+                    circuit_open: e.is_open(now),
+                })
+                .collect()
+        })
+    }
+
+    /// Calls getSlot on every endpoint of the network, recording slot height and latency
+    pub fn check_health(&self, network: Network) {
+        let urls = self.with_endpoints(network, |endpoints| {
+            endpoints
+                .iter()
+                .map(|e| e.url.clone())
+                .collect::<Vec<String>>()
+        });
+
+        for url in urls {
+            let rpc = RpcClient::new_with_timeout_and_commitment(
+                url.clone(),
+                HEALTH_CHECK_TIMEOUT,
+                CommitmentConfig::confirmed(),
+            );
+            let started = Instant::now();
+
+            match rpc.get_slot() {
+                Ok(slot) => self.record_health(network, &url, slot, started.elapsed()),
+                Err(e) => {
+                    tracing::event!(Level::WARN, "Health check of {} failed: {}", url, e);
+                    self.record_failure(network, &url);
+                }
+            }
+        }
+    }
+}
+
+/// Urls configured for the network in `<NETWORK>_RPC_URLS`, e.g. MAINNET_RPC_URLS
+fn configured_urls(network: Network) -> Vec<String> {
+    let urls = configured_urls_from(network, "RPC_URLS");
+
+    if urls.is_empty() {
+        vec![network.default_url()]
+    } else {
+        urls
+    }
+}
+
+/// Comma separated urls of the `<NETWORK>_<suffix>` env var, empty when it isn't set
+fn configured_urls_from(network: Network, suffix: &str) -> Vec<String> {
+    let key = format!("{}_{}", Network::fetch_str(network).to_uppercase(), suffix);
+
+    env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Maps an rpc url to the pubsub url served next to it. http(s) becomes ws(s), and an
+/// explicit port is bumped by one the way solana-validator serves pubsub (8899 -> 8900).
+pub fn pubsub_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some(("http", rest)) => ("ws", rest),
+        Some((scheme, rest)) => (scheme, rest),
+        None => ("ws", rpc_url),
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{}:{}", host, port.saturating_add(1)),
+            Err(_e) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+
+    format!("{}://{}{}", scheme, authority, path)
+}
+
+/// Errors worth retrying on another attempt: connection problems, timeouts, HTTP errors and
+/// nodes reporting themselves as unhealthy
+pub fn is_transient_error(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+/// Runs a blocking RPC call against the healthiest endpoint of the network. Transient errors
+/// are retried with backoff, moving on to the next ranked endpoint on every attempt.
+pub fn with_failover<T, F>(network: &Network, call: F) -> Result<T, ClientError>
+where
+    F: Fn(&str) -> Result<T, ClientError>,
+{
+    let urls = ENDPOINT_POOL.ranked_urls(*network);
+    let mut backoff = RPC_RETRY_BACKOFF;
+    let mut attempt = 0;
+
+    loop {
+        let url = &urls[attempt % urls.len()];
+
+        match call(url) {
+            Ok(value) => {
+                ENDPOINT_POOL.record_success(*network, url);
+                return Ok(value);
+            }
+
+            Err(e) if is_transient_error(&e) => {
+                ENDPOINT_POOL.record_failure(*network, url);
+                attempt += 1;
+
+                tracing::event!(
+                    Level::WARN,
+                    "RPC call to {} failed - Attempt {}/{}",
+                    url,
+                    attempt,
+                    RPC_MAX_ATTEMPTS
+                );
+
+                if attempt >= RPC_MAX_ATTEMPTS {
+                    return Err(e);
+                }
+
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+
+            // The endpoint answered, the request itself was bad
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Opens a pubsub subscription on the first ranked pubsub url of the network that accepts it.
+/// The last error is returned when none does.
+pub fn with_ws_failover<T, F>(network: &Network, connect: F) -> Result<T, PubsubClientError>
+where
+    F: Fn(&str) -> Result<T, PubsubClientError>,
+{
+    let mut last_error = None;
+
+    for url in ENDPOINT_POOL.ranked_ws_urls(*network).iter() {
+        match connect(url) {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                tracing::event!(Level::WARN, "Pubsub connection to {} failed: {}", url, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        PubsubClientError::UnexpectedMessageError("No pubsub urls configured".to_string())
+    }))
+}
+
+/// Health checks the endpoints of the given networks every interval
+pub async fn run_health_checks(networks: Vec<Network>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        for network in networks.iter().copied() {
+            if tokio::task::spawn_blocking(move || ENDPOINT_POOL.check_health(network))
+                .await
+                .is_err()
+            {
+                tracing::event!(Level::ERROR, "Health check of {:?} panicked", network);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pubsub_url_swaps_the_scheme() {
+        assert_eq!(
+            pubsub_url("https://solana-api.projectserum.com"),
+            "wss://solana-api.projectserum.com"
+        );
+        assert_eq!(
+            pubsub_url("https://rpc.example.com/v1/key"),
+            "wss://rpc.example.com/v1/key"
+        );
+    }
+
+    #[test]
+    fn pubsub_url_bumps_an_explicit_port() {
+        assert_eq!(pubsub_url("http://localhost:8899"), "ws://localhost:8900");
+        assert_eq!(
+            pubsub_url("http://10.0.0.1:8899/path"),
+            "ws://10.0.0.1:8900/path"
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use crate::utils::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes};
+
+/// Equality filter on a named account field as sent by HTTP clients
+///
+/// {
+///     "field": "authority", ~ any field name of the account's wrapper
+///     "value": [...] ~ pubkeys as an array of 32 unsigned 8-bit integers or a base58 string
+/// }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FieldFilter {
+    pub field: String,
+    pub value: Value,
+}
+
+/// Builds getProgramAccounts Memcmp filters for one quarry_mine account type from field names,
+/// using the offsets in layout_utils. The account's discriminator filter is always included,
+/// so scans never match other account types.
+///
+/// let filters = AccountFilters::new(AccountType::Miner)
+///     .equals("authority", &FieldValue::Pubkey(authority))?
+///     .build();
+pub struct AccountFilters {
+    account_type: AccountType,
+    memcmps: Vec<Memcmp>,
+}
+
+impl AccountFilters {
+    pub fn new(account_type: AccountType) -> Self {
+        Self {
+            account_type,
+            memcmps: vec![Memcmp {
+                offset: 0,
+                bytes: MemcmpEncodedBytes::Bytes(account_type.discriminator().to_vec()),
+                encoding: None,
+            }],
+        }
+    }
+
+    /// Adds a filter matching accounts whose field equals the value. Fails with a message when
+    /// the field doesn't exist on the account type or the value doesn't fit the field.
+    pub fn equals(mut self, field: &str, value: &FieldValue) -> Result<Self, String> {
+        let layout = self
+            .account_type
+            .field(field)
+            .ok_or_else(|| format!("Unknown {:?} field: {}", self.account_type, field))?;
+
+        let bytes = layout
+            .field_type
+            .encode(value)
+            .ok_or_else(|| format!("Invalid value for {:?} field: {}", self.account_type, field))?;
+
+        self.memcmps.push(Memcmp {
+            offset: layout.offset,
+            bytes: MemcmpEncodedBytes::Bytes(bytes),
+            encoding: None,
+        });
+
+        Ok(self)
+    }
+
+    /// Adds every filter of an HTTP request, parsing each JSON value as its field's type
+    pub fn from_request(
+        account_type: AccountType,
+        filters: &[FieldFilter],
+    ) -> Result<Self, String> {
+        let mut account_filters = Self::new(account_type);
+
+        for filter in filters.iter() {
+            let value = account_type
+                .field(&filter.field)
+                .and_then(|layout| layout.field_type.parse(&filter.value))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid filter on {:?} field: {}",
+                        account_type, filter.field
+                    )
+                })?;
+
+            account_filters = account_filters.equals(&filter.field, &value)?;
+        }
+
+        Ok(account_filters)
+    }
+
+    /// Number of field filters added, not counting the discriminator
+    pub fn field_filter_count(&self) -> usize {
+        self.memcmps.len() - 1
+    }
+
+    pub fn build(self) -> Vec<Memcmp> {
+        self.memcmps
+    }
+}
@@ -0,0 +1,581 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentLevel;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::Level;
+
+use crate::utils::*;
+use crate::{MinerWrapper, QuarryWrapper, RewarderWrapper};
+
+/// Default seconds between index runs, override with INDEXER_INTERVAL_SECONDS
+pub const DEFAULT_INDEXER_INTERVAL_SECONDS: u64 = 300;
+
+/// How often the indexer records the current slot of every indexed network, which the
+/// index_status lag of indexed responses is computed from
+pub const INDEXER_SLOT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+const INDEX_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        network TEXT NOT NULL,
+        slot INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        rewarders INTEGER NOT NULL,
+        quarries INTEGER NOT NULL,
+        miners INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS snapshots_network ON snapshots (network, id);
+
+    CREATE TABLE IF NOT EXISTS rewarder_snapshots (
+        snapshot_id INTEGER NOT NULL,
+        pubkey TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (snapshot_id, pubkey)
+    );
+    CREATE INDEX IF NOT EXISTS rewarder_snapshots_pubkey ON rewarder_snapshots (pubkey);
+
+    CREATE TABLE IF NOT EXISTS quarry_snapshots (
+        snapshot_id INTEGER NOT NULL,
+        pubkey TEXT NOT NULL,
+        rewarder TEXT NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (snapshot_id, pubkey)
+    );
+    CREATE INDEX IF NOT EXISTS quarry_snapshots_pubkey ON quarry_snapshots (pubkey);
+    CREATE INDEX IF NOT EXISTS quarry_snapshots_rewarder ON quarry_snapshots (rewarder, snapshot_id);
+
+    CREATE TABLE IF NOT EXISTS miners (
+        network TEXT NOT NULL,
+        pubkey TEXT NOT NULL,
+        quarry TEXT NOT NULL,
+        authority TEXT NOT NULL,
+        snapshot_id INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (network, pubkey)
+    );
+    CREATE INDEX IF NOT EXISTS miners_quarry ON miners (network, quarry);
+
+    CREATE TABLE IF NOT EXISTS miner_history (
+        network TEXT NOT NULL,
+        pubkey TEXT NOT NULL,
+        quarry TEXT NOT NULL,
+        authority TEXT NOT NULL,
+        snapshot_id INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (network, pubkey, snapshot_id)
+    );
+    CREATE INDEX IF NOT EXISTS miner_history_authority ON miner_history (network, authority);
+";
+
+/// Width of the buckets a historical series is sampled at
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SeriesResolution {
+    Hourly,
+    Daily,
+}
+
+impl SeriesResolution {
+    pub fn seconds(self) -> i64 {
+        match self {
+            Self::Hourly => 3_600,
+            Self::Daily => 86_400,
+        }
+    }
+}
+
+/// A completed index run
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexSnapshot {
+    pub id: i64,
+    pub network: Network,
+    /// Slot observed right before the program account scan
+    pub slot: u64,
+    pub timestamp: i64,
+    pub rewarders: u64,
+    pub quarries: u64,
+    pub miners: u64,
+}
+
+/// How far the index serving a response lags the chain
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexStatus {
+    pub slot: u64,
+    pub indexed_at: i64,
+    /// Slots between the snapshot and the chain slot the indexer last recorded, None until
+    /// one was recorded
+    pub slot_lag: Option<u64>,
+    pub seconds_behind: i64,
+}
+
+impl IndexStatus {
+    pub fn new(snapshot: &IndexSnapshot, chain_slot: Option<u64>) -> Self {
+        Self {
+            slot: snapshot.slot,
+            indexed_at: snapshot.timestamp,
+            slot_lag: chain_slot.map(|slot| slot.saturating_sub(snapshot.slot)),
+            seconds_behind: current_unix_timestamp() - snapshot.timestamp,
+        }
+    }
+}
+
+/// Slot-stamped snapshots of every Quarry, Miner and Rewarder account in an embedded SQLite
+/// database. Quarries and rewarders are stored on every run. Miners are far more numerous, so
+/// a new row is only written to miner_history when a miner changed since the previous run,
+/// and the miners table only holds the miners seen by the latest run.
+pub struct QuarryIndex {
+    /// Only used by the indexer, each run is written in a single transaction
+    writer: Mutex<Connection>,
+    /// Serves every read. The database is in WAL mode, so reads see the last committed run
+    /// instead of waiting for the one being written.
+    reader: Mutex<Connection>,
+    /// Latest slot the indexer observed on each network
+    chain_slots: Mutex<HashMap<Network, u64>>,
+}
+
+impl QuarryIndex {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        let writer = Connection::open(path.as_ref())?;
+        writer.query_row("PRAGMA journal_mode = WAL", [], |row| {
+            row.get::<_, String>(0)
+        })?;
+        writer.execute_batch(INDEX_SCHEMA)?;
+
+        let reader = Connection::open(path.as_ref())?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+            reader: Mutex::new(reader),
+            chain_slots: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn record_chain_slot(&self, network: Network, slot: u64) {
+        let mut chain_slots = self.chain_slots.lock().unwrap();
+        let recorded = chain_slots.entry(network).or_insert(slot);
+        *recorded = (*recorded).max(slot);
+    }
+
+    /// The latest slot the indexer observed on the network, None if it never did
+    pub fn chain_slot(&self, network: Network) -> Option<u64> {
+        self.chain_slots.lock().unwrap().get(&network).copied()
+    }
+
+    /// Stores the result of a full program account scan as a new snapshot
+    pub fn record_snapshot(
+        &self,
+        network: Network,
+        slot: u64,
+        timestamp: i64,
+        accounts: &[(Pubkey, QuarryAccount)],
+    ) -> rusqlite::Result<IndexSnapshot> {
+        // Serializing every account is the slow part, do it before taking the writer
+        let rows: Vec<(String, &QuarryAccount, String)> = accounts
+            .iter()
+            .map(|(pubkey, account)| {
+                let data = match account {
+                    QuarryAccount::Rewarder(rewarder) => to_json(rewarder),
+                    QuarryAccount::Quarry(quarry) => to_json(quarry),
+                    QuarryAccount::Miner(miner) => to_json(miner),
+                };
+
+                (pubkey.to_string(), account, data)
+            })
+            .collect();
+
+        let mut conn = self.writer.lock().unwrap();
+        let tx = conn.transaction()?;
+        let network_str = Network::fetch_str(network);
+
+        tx.execute(
+            "INSERT INTO snapshots (network, slot, timestamp, rewarders, quarries, miners)
+             VALUES (?1, ?2, ?3, 0, 0, 0)",
+            params![network_str, slot as i64, timestamp],
+        )?;
+        let snapshot_id = tx.last_insert_rowid();
+
+        let (mut rewarders, mut quarries, mut miners) = (0u64, 0u64, 0u64);
+
+        // Miners missing from the scan were closed and are pruned from the current state
+        tx.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS scanned_miners (pubkey TEXT PRIMARY KEY);
+             DELETE FROM scanned_miners;",
+        )?;
+
+        {
+            let mut insert_rewarder = tx.prepare(
+                "INSERT INTO rewarder_snapshots (snapshot_id, pubkey, data) VALUES (?1, ?2, ?3)",
+            )?;
+            let mut insert_quarry = tx.prepare(
+                "INSERT INTO quarry_snapshots (snapshot_id, pubkey, rewarder, data)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut current_miner =
+                tx.prepare("SELECT data FROM miners WHERE network = ?1 AND pubkey = ?2")?;
+            let mut upsert_miner = tx.prepare(
+                "INSERT OR REPLACE INTO miners (network, pubkey, quarry, authority, snapshot_id, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut insert_miner_history = tx.prepare(
+                "INSERT INTO miner_history (network, pubkey, quarry, authority, snapshot_id, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut insert_scanned_miner =
+                tx.prepare("INSERT OR IGNORE INTO scanned_miners (pubkey) VALUES (?1)")?;
+
+            for (key, account, data) in rows.iter() {
+                match account {
+                    QuarryAccount::Rewarder(_) => {
+                        insert_rewarder.execute(params![snapshot_id, key, data])?;
+                        rewarders += 1;
+                    }
+
+                    QuarryAccount::Quarry(quarry) => {
+                        insert_quarry.execute(params![
+                            snapshot_id,
+                            key,
+                            quarry.rewarder_key.to_string(),
+                            data
+                        ])?;
+                        quarries += 1;
+                    }
+
+                    QuarryAccount::Miner(miner) => {
+                        let previous: Option<String> = current_miner
+                            .query_row(params![network_str, key], |row| row.get(0))
+                            .optional()?;
+
+                        if previous.as_deref() != Some(data.as_str()) {
+                            let quarry_key = miner.quarry_key.to_string();
+                            let authority = miner.authority.to_string();
+                            let row =
+                                params![network_str, key, quarry_key, authority, snapshot_id, data];
+                            upsert_miner.execute(row)?;
+                            insert_miner_history.execute(row)?;
+                        }
+
+                        insert_scanned_miner.execute(params![key])?;
+                        miners += 1;
+                    }
+                }
+            }
+        }
+
+        tx.execute(
+            "DELETE FROM miners WHERE network = ?1
+             AND pubkey NOT IN (SELECT pubkey FROM scanned_miners)",
+            params![network_str],
+        )?;
+
+        tx.execute(
+            "UPDATE snapshots SET rewarders = ?1, quarries = ?2, miners = ?3 WHERE id = ?4",
+            params![
+                rewarders as i64,
+                quarries as i64,
+                miners as i64,
+                snapshot_id
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(IndexSnapshot {
+            id: snapshot_id,
+            network,
+            slot,
+            timestamp,
+            rewarders,
+            quarries,
+            miners,
+        })
+    }
+
+    /// The most recent completed run for the network, None if it was never indexed
+    pub fn latest_snapshot(&self, network: Network) -> rusqlite::Result<Option<IndexSnapshot>> {
+        let conn = self.reader.lock().unwrap();
+
+        conn.query_row(
+            "SELECT id, slot, timestamp, rewarders, quarries, miners FROM snapshots
+             WHERE network = ?1 ORDER BY id DESC LIMIT 1",
+            params![Network::fetch_str(network)],
+            |row| {
+                Ok(IndexSnapshot {
+                    id: row.get(0)?,
+                    network,
+                    slot: row.get::<_, i64>(1)? as u64,
+                    timestamp: row.get(2)?,
+                    rewarders: row.get::<_, i64>(3)? as u64,
+                    quarries: row.get::<_, i64>(4)? as u64,
+                    miners: row.get::<_, i64>(5)? as u64,
+                })
+            },
+        )
+        .optional()
+    }
+
+    /// Every run of the network between start_ts and end_ts inclusive, oldest first
+    pub fn snapshots_in_range(
+        &self,
+        network: Network,
+        start_ts: i64,
+        end_ts: i64,
+    ) -> rusqlite::Result<Vec<IndexSnapshot>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, slot, timestamp, rewarders, quarries, miners FROM snapshots
+             WHERE network = ?1 AND timestamp >= ?2 AND timestamp <= ?3 ORDER BY id ASC",
+        )?;
+
+        let rows = stmt.query_map(
+            params![Network::fetch_str(network), start_ts, end_ts],
+            |row| {
+                Ok(IndexSnapshot {
+                    id: row.get(0)?,
+                    network,
+                    slot: row.get::<_, i64>(1)? as u64,
+                    timestamp: row.get(2)?,
+                    rewarders: row.get::<_, i64>(3)? as u64,
+                    quarries: row.get::<_, i64>(4)? as u64,
+                    miners: row.get::<_, i64>(5)? as u64,
+                })
+            },
+        )?;
+
+        rows.collect()
+    }
+
+    pub fn rewarder(
+        &self,
+        snapshot: &IndexSnapshot,
+        pubkey: &Pubkey,
+    ) -> rusqlite::Result<Option<RewarderWrapper>> {
+        let conn = self.reader.lock().unwrap();
+
+        let data: Option<String> = conn
+            .query_row(
+                "SELECT data FROM rewarder_snapshots WHERE snapshot_id = ?1 AND pubkey = ?2",
+                params![snapshot.id, pubkey.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+    }
+
+    pub fn quarry(
+        &self,
+        snapshot: &IndexSnapshot,
+        pubkey: &Pubkey,
+    ) -> rusqlite::Result<Option<QuarryWrapper>> {
+        let conn = self.reader.lock().unwrap();
+
+        let data: Option<String> = conn
+            .query_row(
+                "SELECT data FROM quarry_snapshots WHERE snapshot_id = ?1 AND pubkey = ?2",
+                params![snapshot.id, pubkey.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(data.and_then(|d| serde_json::from_str(&d).ok()))
+    }
+
+    pub fn quarries_for_rewarder(
+        &self,
+        snapshot: &IndexSnapshot,
+        rewarder: &Pubkey,
+    ) -> rusqlite::Result<Vec<(Pubkey, QuarryWrapper)>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT pubkey, data FROM quarry_snapshots WHERE snapshot_id = ?1 AND rewarder = ?2",
+        )?;
+
+        let rows = stmt.query_map(params![snapshot.id, rewarder.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        Ok(rows
+            .filter_map(|row| row.ok())
+            .filter_map(|(key, data)| parse_row(&key, &data))
+            .collect())
+    }
+
+    /// Every recorded version of every miner owned by the authority, as (miner, snapshot id,
+    /// state) ordered by miner then snapshot. A version holds until the next one.
+    pub fn miner_history_for_authority(
+        &self,
+        network: Network,
+        authority: &Pubkey,
+    ) -> rusqlite::Result<Vec<(Pubkey, i64, MinerWrapper)>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT pubkey, snapshot_id, data FROM miner_history
+             WHERE network = ?1 AND authority = ?2 ORDER BY pubkey, snapshot_id",
+        )?;
+
+        let rows = stmt.query_map(
+            params![Network::fetch_str(network), authority.to_string()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )?;
+
+        Ok(rows
+            .filter_map(|row| row.ok())
+            .filter_map(|(key, snapshot_id, data)| {
+                parse_row(&key, &data).map(|(key, miner)| (key, snapshot_id, miner))
+            })
+            .collect())
+    }
+
+    /// The latest indexed state of every miner of the quarry
+    pub fn miners_for_quarry(
+        &self,
+        network: Network,
+        quarry: &Pubkey,
+    ) -> rusqlite::Result<Vec<(Pubkey, MinerWrapper)>> {
+        let conn = self.reader.lock().unwrap();
+        let mut stmt =
+            conn.prepare("SELECT pubkey, data FROM miners WHERE network = ?1 AND quarry = ?2")?;
+
+        let rows = stmt.query_map(
+            params![Network::fetch_str(network), quarry.to_string()],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )?;
+
+        Ok(rows
+            .filter_map(|row| row.ok())
+            .filter_map(|(key, data)| parse_row(&key, &data))
+            .collect())
+    }
+}
+
+/// Keeps the last snapshot of every resolution sized bucket. Expects snapshots oldest first.
+pub fn sample_snapshots(
+    snapshots: Vec<IndexSnapshot>,
+    resolution: SeriesResolution,
+) -> Vec<IndexSnapshot> {
+    let width = resolution.seconds();
+    let mut sampled: Vec<IndexSnapshot> = Vec::new();
+
+    for snapshot in snapshots.into_iter() {
+        let same_bucket = sampled
+            .last()
+            .map(|last| last.timestamp.div_euclid(width) == snapshot.timestamp.div_euclid(width))
+            .unwrap_or(false);
+
+        if same_bucket {
+            sampled.pop();
+        }
+
+        sampled.push(snapshot);
+    }
+
+    sampled
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn parse_row<T: for<'de> Deserialize<'de>>(key: &str, data: &str) -> Option<(Pubkey, T)> {
+    Some((
+        Pubkey::from_str(key).ok()?,
+        serde_json::from_str(data).ok()?,
+    ))
+}
+
+/// Scans every quarry_mine account of a network and records it as a new snapshot
+pub fn index_network(index: &QuarryIndex, network: Network) -> Option<IndexSnapshot> {
+    let (accounts, slot) = match fetch_program_accounts_with_slot(
+        &network,
+        &quarry_mine::ID,
+        None,
+        CommitmentLevel::Confirmed,
+    ) {
+        Ok(value) => {
+            tracing::event!(Level::INFO, "Indexer account scan successful - Step 1/2");
+            index.record_chain_slot(network, value.1);
+            value
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Indexer account scan failed - Step 1/2");
+            return None;
+        }
+    };
+
+    let decoded: Vec<(Pubkey, QuarryAccount)> = accounts
+        .iter()
+        .filter_map(|(pubkey, account)| QuarryAccount::decode(account).map(|a| (*pubkey, a)))
+        .collect();
+
+    match index.record_snapshot(network, slot, current_unix_timestamp(), &decoded) {
+        Ok(value) => {
+            tracing::event!(
+                Level::INFO,
+                "Indexer snapshot {} stored at slot {} - Step 2/2",
+                value.id,
+                value.slot
+            );
+            Some(value)
+        }
+
+        Err(_e) => {
+            tracing::event!(Level::ERROR, "Indexer snapshot store failed - Step 2/2");
+            None
+        }
+    }
+}
+
+/// Re-indexes every network forever, one run per interval. The current slot of every network
+/// is recorded more often in between.
+pub async fn run_indexer(index: Arc<QuarryIndex>, networks: Vec<Network>, interval: Duration) {
+    tokio::spawn(track_chain_slots(index.clone(), networks.clone()));
+
+    loop {
+        for network in networks.iter().copied() {
+            let index = index.clone();
+            // RPC and SQLite calls are blocking, keep them off the async workers
+            let _ = tokio::task::spawn_blocking(move || index_network(&index, network)).await;
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Records the current slot of every network once per INDEXER_SLOT_POLL_INTERVAL, so indexed
+/// responses can report their lag without an RPC call of their own
+async fn track_chain_slots(index: Arc<QuarryIndex>, networks: Vec<Network>) {
+    loop {
+        for network in networks.iter().copied() {
+            let index = index.clone();
+            let _ = tokio::task::spawn_blocking(move || match fetch_slot(&network) {
+                Ok(slot) => index.record_chain_slot(network, slot),
+                Err(_e) => tracing::event!(Level::ERROR, "Indexer slot poll failed"),
+            })
+            .await;
+        }
+
+        tokio::time::sleep(INDEXER_SLOT_POLL_INTERVAL).await;
+    }
+}
+
+/// The index and its latest snapshot of the network, if indexer mode is on and the network
+/// has been indexed at least once
+pub fn latest_index_snapshot(
+    index: &Option<Arc<QuarryIndex>>,
+    network: Network,
+) -> Option<(Arc<QuarryIndex>, IndexSnapshot)> {
+    let index = index.as_ref()?;
+    let snapshot = index.latest_snapshot(network).ok()??;
+
+    Some((index.clone(), snapshot))
+}
@@ -0,0 +1,408 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_program::hash::hash;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_program::{system_program, sysvar};
+
+use crate::utils::*;
+
+/// An account passed to an instruction along with the name the program gives it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedAccountMeta {
+    pub name: String,
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl NamedAccountMeta {
+    pub fn writable(name: &str, pubkey: Pubkey, is_signer: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            pubkey,
+            is_signer,
+            is_writable: true,
+        }
+    }
+
+    pub fn readonly(name: &str, pubkey: Pubkey, is_signer: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            pubkey,
+            is_signer,
+            is_writable: false,
+        }
+    }
+}
+
+/// An instruction along with a readable description of what it does, used both to build
+/// transactions and to summarize them for clients
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedInstruction {
+    pub program: String,
+    pub program_id: Pubkey,
+    pub name: String,
+    pub accounts: Vec<NamedAccountMeta>,
+    pub args: Value,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl NamedInstruction {
+    pub fn to_instruction(&self) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: self
+                .accounts
+                .iter()
+                .map(|a| AccountMeta {
+                    pubkey: a.pubkey,
+                    is_signer: a.is_signer,
+                    is_writable: a.is_writable,
+                })
+                .collect(),
+            data: self.data.clone(),
+        }
+    }
+}
+
+/// Anchor instruction discriminator: the first 8 bytes of sha256("global:<name>")
+pub fn anchor_sighash(name: &str) -> [u8; 8] {
+    let mut sighash = [0u8; 8];
+    sighash.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    sighash
+}
+
+/// Builds a quarry_mine instruction from its snake_case name, accounts and borsh encoded args
+fn quarry_mine_instruction(
+    name: &str,
+    accounts: Vec<NamedAccountMeta>,
+    encoded_args: &[u8],
+    args: Value,
+) -> NamedInstruction {
+    let mut data = anchor_sighash(name).to_vec();
+    data.extend_from_slice(encoded_args);
+
+    NamedInstruction {
+        program: "quarry_mine".to_string(),
+        program_id: quarry_mine::ID,
+        name: name.to_string(),
+        accounts,
+        args,
+        data,
+    }
+}
+
+/// Creates the associated token account of `owner` for `mint`, paid for by `payer`
+pub fn create_associated_token_account_ix(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> NamedInstruction {
+    let ix = spl_associated_token_account::create_associated_token_account(payer, owner, mint);
+
+    // Names follow the account order of spl_associated_token_account::create_associated_token_account
+    let names = [
+        "funding_address",
+        "associated_token_address",
+        "wallet_address",
+        "token_mint_address",
+        "system_program",
+        "token_program",
+        "rent_sysvar",
+    ];
+
+    NamedInstruction {
+        program: "spl_associated_token_account".to_string(),
+        program_id: ix.program_id,
+        name: "create".to_string(),
+        accounts: ix
+            .accounts
+            .iter()
+            .zip(names.iter())
+            .map(|(meta, name)| NamedAccountMeta {
+                name: name.to_string(),
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        args: json!({}),
+        data: ix.data,
+    }
+}
+
+/// quarry_mine::create_miner. The miner vault must already exist and be owned by the miner.
+pub fn create_miner_ix(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    quarry: &Pubkey,
+    rewarder: &Pubkey,
+    token_mint: &Pubkey,
+) -> NamedInstruction {
+    let (miner, bump) = find_miner_address(quarry, authority);
+    let miner_vault = find_miner_vault_address(&miner, token_mint);
+
+    quarry_mine_instruction(
+        "create_miner",
+        vec![
+            NamedAccountMeta::readonly("authority", *authority, true),
+            NamedAccountMeta::writable("miner", miner, false),
+            NamedAccountMeta::writable("quarry", *quarry, false),
+            NamedAccountMeta::readonly("rewarder", *rewarder, false),
+            NamedAccountMeta::readonly("system_program", system_program::ID, false),
+            NamedAccountMeta::writable("payer", *payer, true),
+            NamedAccountMeta::readonly("token_mint", *token_mint, false),
+            NamedAccountMeta::readonly("miner_vault", miner_vault, false),
+            NamedAccountMeta::readonly("token_program", spl_token::ID, false),
+        ],
+        &[bump],
+        json!({ "bump": bump }),
+    )
+}
+
+/// Accounts shared by stake_tokens, withdraw_tokens and the stake half of claim_rewards, in
+/// the order of quarry_mine::UserStake
+fn user_stake_accounts(
+    authority: &Pubkey,
+    quarry: &Pubkey,
+    rewarder: &Pubkey,
+    token_mint: &Pubkey,
+    token_account: &Pubkey,
+) -> Vec<NamedAccountMeta> {
+    let (miner, _bump) = find_miner_address(quarry, authority);
+    let miner_vault = find_miner_vault_address(&miner, token_mint);
+
+    vec![
+        NamedAccountMeta::readonly("authority", *authority, true),
+        NamedAccountMeta::writable("miner", miner, false),
+        NamedAccountMeta::writable("quarry", *quarry, false),
+        NamedAccountMeta::writable("miner_vault", miner_vault, false),
+        NamedAccountMeta::writable("token_account", *token_account, false),
+        NamedAccountMeta::readonly("token_program", spl_token::ID, false),
+        NamedAccountMeta::readonly("rewarder", *rewarder, false),
+    ]
+}
+
+/// quarry_mine::stake_tokens, moving `amount` from `token_account` into the miner vault
+pub fn stake_tokens_ix(
+    authority: &Pubkey,
+    quarry: &Pubkey,
+    rewarder: &Pubkey,
+    token_mint: &Pubkey,
+    token_account: &Pubkey,
+    amount: u64,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "stake_tokens",
+        user_stake_accounts(authority, quarry, rewarder, token_mint, token_account),
+        &amount.to_le_bytes(),
+        json!({ "amount": amount }),
+    )
+}
+
+/// quarry_mine::withdraw_tokens, moving `amount` from the miner vault into `token_account`
+pub fn withdraw_tokens_ix(
+    authority: &Pubkey,
+    quarry: &Pubkey,
+    rewarder: &Pubkey,
+    token_mint: &Pubkey,
+    token_account: &Pubkey,
+    amount: u64,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "withdraw_tokens",
+        user_stake_accounts(authority, quarry, rewarder, token_mint, token_account),
+        &amount.to_le_bytes(),
+        json!({ "amount": amount }),
+    )
+}
+
+/// Rewarder fields needed to build a claim
+pub struct ClaimRewarderAccounts {
+    pub rewarder: Pubkey,
+    pub mint_wrapper: Pubkey,
+    pub rewards_token_mint: Pubkey,
+    pub claim_fee_token_account: Pubkey,
+}
+
+/// quarry_mine::claim_rewards, minting everything the miner has earned into
+/// `rewards_token_account` through the rewarder's mint wrapper
+pub fn claim_rewards_ix(
+    authority: &Pubkey,
+    quarry: &Pubkey,
+    token_mint: &Pubkey,
+    staked_token_account: &Pubkey,
+    rewards_token_account: &Pubkey,
+    rewarder: &ClaimRewarderAccounts,
+) -> NamedInstruction {
+    let (minter, _bump) = find_minter_address(&rewarder.mint_wrapper, &rewarder.rewarder);
+
+    let mut accounts = vec![
+        NamedAccountMeta::writable("mint_wrapper", rewarder.mint_wrapper, false),
+        NamedAccountMeta::readonly("mint_wrapper_program", quarry_mint_wrapper::ID, false),
+        NamedAccountMeta::writable("minter", minter, false),
+        NamedAccountMeta::writable("rewards_token_mint", rewarder.rewards_token_mint, false),
+        NamedAccountMeta::writable("rewards_token_account", *rewards_token_account, false),
+        NamedAccountMeta::writable(
+            "claim_fee_token_account",
+            rewarder.claim_fee_token_account,
+            false,
+        ),
+    ];
+
+    // The nested UserStake accounts are flattened after the claim accounts
+    accounts.extend(
+        user_stake_accounts(
+            authority,
+            quarry,
+            &rewarder.rewarder,
+            token_mint,
+            staked_token_account,
+        )
+        .into_iter()
+        .map(|mut account| {
+            account.name = format!("stake.{}", account.name);
+            account
+        }),
+    );
+
+    quarry_mine_instruction("claim_rewards", accounts, &[], json!({}))
+}
+
+/// quarry_mine::set_annual_rewards, signed by the rewarder authority
+pub fn set_annual_rewards_ix(
+    authority: &Pubkey,
+    rewarder: &Pubkey,
+    new_rate: u64,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "set_annual_rewards",
+        vec![
+            NamedAccountMeta::readonly("auth.authority", *authority, true),
+            NamedAccountMeta::writable("auth.rewarder", *rewarder, false),
+        ],
+        &new_rate.to_le_bytes(),
+        json!({ "new_rate": new_rate }),
+    )
+}
+
+/// quarry_mine::set_rewards_share, signed by the rewarder authority
+pub fn set_rewards_share_ix(
+    authority: &Pubkey,
+    rewarder: &Pubkey,
+    quarry: &Pubkey,
+    new_share: u64,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "set_rewards_share",
+        vec![
+            NamedAccountMeta::readonly("auth.authority", *authority, true),
+            NamedAccountMeta::writable("auth.rewarder", *rewarder, false),
+            NamedAccountMeta::writable("quarry", *quarry, false),
+        ],
+        &new_share.to_le_bytes(),
+        json!({ "new_share": new_share }),
+    )
+}
+
+/// quarry_mine::set_famine, signed by the rewarder authority
+pub fn set_famine_ix(
+    authority: &Pubkey,
+    rewarder: &Pubkey,
+    quarry: &Pubkey,
+    famine_ts: i64,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "set_famine",
+        vec![
+            NamedAccountMeta::readonly("auth.authority", *authority, true),
+            NamedAccountMeta::readonly("auth.rewarder", *rewarder, false),
+            NamedAccountMeta::writable("quarry", *quarry, false),
+        ],
+        &famine_ts.to_le_bytes(),
+        json!({ "famine_ts": famine_ts }),
+    )
+}
+
+/// quarry_mine::create_quarry, signed by the rewarder authority and paid for by `payer`
+pub fn create_quarry_ix(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    rewarder: &Pubkey,
+    token_mint: &Pubkey,
+) -> NamedInstruction {
+    let (quarry, bump) = find_quarry_address(rewarder, token_mint);
+
+    quarry_mine_instruction(
+        "create_quarry",
+        vec![
+            NamedAccountMeta::writable("quarry", quarry, false),
+            NamedAccountMeta::readonly("auth.authority", *authority, true),
+            NamedAccountMeta::writable("auth.rewarder", *rewarder, false),
+            NamedAccountMeta::readonly("token_mint", *token_mint, false),
+            NamedAccountMeta::writable("payer", *payer, true),
+            NamedAccountMeta::readonly("clock", sysvar::clock::ID, false),
+            NamedAccountMeta::readonly("system_program", system_program::ID, false),
+        ],
+        &[bump],
+        json!({ "bump": bump }),
+    )
+}
+
+/// quarry_mine::pause, signed by the rewarder pause authority
+pub fn pause_ix(pause_authority: &Pubkey, rewarder: &Pubkey) -> NamedInstruction {
+    quarry_mine_instruction(
+        "pause",
+        vec![
+            NamedAccountMeta::readonly("pause_authority", *pause_authority, true),
+            NamedAccountMeta::writable("rewarder", *rewarder, false),
+        ],
+        &[],
+        json!({}),
+    )
+}
+
+/// quarry_mine::unpause, signed by the rewarder pause authority
+pub fn unpause_ix(pause_authority: &Pubkey, rewarder: &Pubkey) -> NamedInstruction {
+    quarry_mine_instruction(
+        "unpause",
+        vec![
+            NamedAccountMeta::readonly("pause_authority", *pause_authority, true),
+            NamedAccountMeta::writable("rewarder", *rewarder, false),
+        ],
+        &[],
+        json!({}),
+    )
+}
+
+/// quarry_mine::transfer_authority, signed by the current rewarder authority
+pub fn transfer_authority_ix(
+    authority: &Pubkey,
+    rewarder: &Pubkey,
+    new_authority: &Pubkey,
+) -> NamedInstruction {
+    quarry_mine_instruction(
+        "transfer_authority",
+        vec![
+            NamedAccountMeta::readonly("authority", *authority, true),
+            NamedAccountMeta::writable("rewarder", *rewarder, false),
+        ],
+        &new_authority.to_bytes(),
+        json!({ "new_authority": new_authority }),
+    )
+}
+
+/// quarry_mine::accept_authority, signed by the rewarder's pending authority
+pub fn accept_authority_ix(pending_authority: &Pubkey, rewarder: &Pubkey) -> NamedInstruction {
+    quarry_mine_instruction(
+        "accept_authority",
+        vec![
+            NamedAccountMeta::readonly("authority", *pending_authority, true),
+            NamedAccountMeta::writable("rewarder", *rewarder, false),
+        ],
+        &[],
+        json!({}),
+    )
+}
@@ -0,0 +1,464 @@
+use anchor_client::anchor_lang::Discriminator;
+use quarry_mine::{Miner, Quarry, Rewarder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_account_decoder::UiDataSliceConfig;
+use solana_program::pubkey::Pubkey;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::str::FromStr;
+
+/// Borsh encoded type of a single account field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    Pubkey,
+    Bool,
+    U8,
+    U16,
+    U64,
+    I64,
+    U128,
+}
+
+impl FieldType {
+    /// Number of bytes the field takes up in account data
+    pub fn size(self) -> usize {
+        match self {
+            Self::Pubkey => 32,
+            Self::Bool | Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U64 | Self::I64 => 8,
+            Self::U128 => 16,
+        }
+    }
+
+    /// Borsh encodes a value of this type, None when the value is of another type or out of
+    /// range
+    pub fn encode(self, value: &FieldValue) -> Option<Vec<u8>> {
+        let bytes = match (self, value) {
+            (Self::Pubkey, FieldValue::Pubkey(pubkey)) => pubkey.to_bytes().to_vec(),
+            (Self::Bool, FieldValue::Bool(b)) => vec![*b as u8],
+            (Self::U8, FieldValue::Unsigned(n)) => vec![u8::try_from(*n).ok()?],
+            (Self::U16, FieldValue::Unsigned(n)) => u16::try_from(*n).ok()?.to_le_bytes().to_vec(),
+            (Self::U64, FieldValue::Unsigned(n)) => u64::try_from(*n).ok()?.to_le_bytes().to_vec(),
+            (Self::U128, FieldValue::Unsigned(n)) => n.to_le_bytes().to_vec(),
+            (Self::I64, FieldValue::Signed(n)) => n.to_le_bytes().to_vec(),
+            (Self::I64, FieldValue::Unsigned(n)) => i64::try_from(*n).ok()?.to_le_bytes().to_vec(),
+            _ => return None,
+        };
+
+        Some(bytes)
+    }
+
+    /// Reads a value of this type from request JSON. Pubkeys may be an array of 32 unsigned
+    /// 8-bit integers or a base58 string, and integers too large for JSON may be strings.
+    pub fn parse(self, value: &Value) -> Option<FieldValue> {
+        match (self, value) {
+            (Self::Pubkey, Value::String(s)) => Pubkey::from_str(s).ok().map(FieldValue::Pubkey),
+            (Self::Pubkey, Value::Array(_)) => serde_json::from_value::<Pubkey>(value.clone())
+                .ok()
+                .map(FieldValue::Pubkey),
+            (Self::Bool, Value::Bool(b)) => Some(FieldValue::Bool(*b)),
+            (Self::I64, Value::Number(n)) => n.as_i64().map(FieldValue::Signed),
+            (Self::I64, Value::String(s)) => s.parse::<i64>().ok().map(FieldValue::Signed),
+            (Self::Pubkey, _) | (Self::Bool, _) | (Self::I64, _) => None,
+            (_, Value::Number(n)) => n.as_u64().map(|n| FieldValue::Unsigned(n as u128)),
+            (_, Value::String(s)) => s.parse::<u128>().ok().map(FieldValue::Unsigned),
+            _ => None,
+        }
+    }
+
+    /// Decodes the field from exactly `size()` bytes
+    pub fn decode(self, bytes: &[u8]) -> Option<FieldValue> {
+        let value = match self {
+            Self::Pubkey => FieldValue::Pubkey(Pubkey::new(bytes)),
+            Self::Bool => FieldValue::Bool(bytes[0] != 0),
+            Self::U8 => FieldValue::Unsigned(bytes[0] as u128),
+            Self::U16 => FieldValue::Unsigned(u16::from_le_bytes(bytes.try_into().ok()?) as u128),
+            Self::U64 => FieldValue::Unsigned(u64::from_le_bytes(bytes.try_into().ok()?) as u128),
+            Self::I64 => FieldValue::Signed(i64::from_le_bytes(bytes.try_into().ok()?)),
+            Self::U128 => FieldValue::Unsigned(u128::from_le_bytes(bytes.try_into().ok()?)),
+        };
+
+        Some(value)
+    }
+}
+
+/// A single decoded field. Serializes the same way the field does on the account wrappers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    Pubkey(Pubkey),
+    Bool(bool),
+    Unsigned(u128),
+    Signed(i64),
+}
+
+impl FieldValue {
+    /// Orders two values of the same kind. Integers compare exactly whether signed or not,
+    /// pubkeys compare by their bytes. Values of different kinds don't compare.
+    pub fn compare(&self, other: &FieldValue) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Pubkey(a), Self::Pubkey(b)) => Some(a.to_bytes().cmp(&b.to_bytes())),
+            (Self::Bool(a), Self::Bool(b)) => Some(a.cmp(b)),
+            (Self::Unsigned(a), Self::Unsigned(b)) => Some(a.cmp(b)),
+            (Self::Signed(a), Self::Signed(b)) => Some(a.cmp(b)),
+            (Self::Signed(a), Self::Unsigned(b)) => Some(if *a < 0 {
+                Ordering::Less
+            } else {
+                (*a as u128).cmp(b)
+            }),
+            (Self::Unsigned(_), Self::Signed(_)) => other.compare(self).map(Ordering::reverse),
+            _ => None,
+        }
+    }
+}
+
+/// Where a named field lives in the account data, offsets include the discriminator
+#[derive(Clone, Copy, Debug)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub offset: usize,
+    pub field_type: FieldType,
+}
+
+const fn field(name: &'static str, offset: usize, field_type: FieldType) -> FieldLayout {
+    FieldLayout {
+        name,
+        offset,
+        field_type,
+    }
+}
+
+/// Serialized layout of quarry_mine::Quarry, in declaration order
+pub const QUARRY_LAYOUT: &[FieldLayout] = &[
+    field("rewarder_key", 8, FieldType::Pubkey),
+    field("token_mint_key", 40, FieldType::Pubkey),
+    field("bump", 72, FieldType::U8),
+    field("index", 73, FieldType::U16),
+    field("token_mint_decimals", 75, FieldType::U8),
+    field("famine_ts", 76, FieldType::I64),
+    field("last_update_ts", 84, FieldType::I64),
+    field("rewards_per_token_stored", 92, FieldType::U128),
+    field("annual_rewards_rate", 108, FieldType::U64),
+    field("rewards_share", 116, FieldType::U64),
+    field("total_tokens_deposited", 124, FieldType::U64),
+    field("num_miners", 132, FieldType::U64),
+];
+
+/// Serialized layout of quarry_mine::Miner, in declaration order
+pub const MINER_LAYOUT: &[FieldLayout] = &[
+    field("quarry_key", 8, FieldType::Pubkey),
+    field("authority", 40, FieldType::Pubkey),
+    field("bump", 72, FieldType::U8),
+    field("token_vault_key", 73, FieldType::Pubkey),
+    field("rewards_earned", 105, FieldType::U64),
+    field("rewards_per_token_paid", 113, FieldType::U128),
+    field("balance", 129, FieldType::U64),
+    field("index", 137, FieldType::U64),
+];
+
+/// Serialized layout of quarry_mine::Rewarder, in declaration order
+pub const REWARDER_LAYOUT: &[FieldLayout] = &[
+    field("base", 8, FieldType::Pubkey),
+    field("bump", 40, FieldType::U8),
+    field("authority", 41, FieldType::Pubkey),
+    field("pending_authority", 73, FieldType::Pubkey),
+    field("num_quarries", 105, FieldType::U16),
+    field("annual_rewards_rate", 107, FieldType::U64),
+    field("total_rewards_shares", 115, FieldType::U64),
+    field("mint_wrapper", 123, FieldType::Pubkey),
+    field("rewards_token_mint", 155, FieldType::Pubkey),
+    field("claim_fee_token_account", 187, FieldType::Pubkey),
+    field("max_claim_fee_kbps", 219, FieldType::U64),
+    field("pause_authority", 227, FieldType::Pubkey),
+    field("is_paused", 259, FieldType::Bool),
+];
+
+/// The quarry_mine account types warlock knows the layout of
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AccountType {
+    Quarry,
+    Miner,
+    Rewarder,
+}
+
+impl AccountType {
+    pub fn layout(self) -> &'static [FieldLayout] {
+        match self {
+            Self::Quarry => QUARRY_LAYOUT,
+            Self::Miner => MINER_LAYOUT,
+            Self::Rewarder => REWARDER_LAYOUT,
+        }
+    }
+
+    pub fn discriminator(self) -> [u8; 8] {
+        match self {
+            Self::Quarry => Quarry::discriminator(),
+            Self::Miner => Miner::discriminator(),
+            Self::Rewarder => Rewarder::discriminator(),
+        }
+    }
+
+    /// Looks up a field by the name it has on the account wrapper
+    pub fn field(self, name: &str) -> Option<&'static FieldLayout> {
+        self.layout().iter().find(|f| f.name == name)
+    }
+}
+
+/// A subset of an account type's fields. Scans using a projection only download the byte
+/// range covering those fields and decode them into a partial view.
+#[derive(Clone, Debug)]
+pub struct Projection {
+    pub fields: Vec<&'static FieldLayout>,
+}
+
+impl Projection {
+    /// Builds a projection from wrapper field names, returning the first unknown name on error.
+    /// An empty projection downloads no data at all, only the account pubkeys.
+    pub fn new(account_type: AccountType, names: &[String]) -> Result<Self, String> {
+        let mut fields = Vec::with_capacity(names.len());
+
+        for name in names.iter() {
+            match account_type.field(name) {
+                Some(field) => fields.push(field),
+                None => return Err(name.clone()),
+            }
+        }
+
+        Ok(Self { fields })
+    }
+
+    /// The single contiguous byte range covering every projected field, as getProgramAccounts
+    /// only accepts one slice per request
+    pub fn data_slice(&self) -> UiDataSliceConfig {
+        let start = self.fields.iter().map(|f| f.offset).min().unwrap_or(0);
+        let end = self
+            .fields
+            .iter()
+            .map(|f| f.offset + f.field_type.size())
+            .max()
+            .unwrap_or(0);
+
+        UiDataSliceConfig {
+            offset: start,
+            length: end - start,
+        }
+    }
+
+    /// Decodes the projected fields from data returned for `data_slice()`
+    pub fn decode(&self, sliced_data: &[u8]) -> Option<BTreeMap<String, FieldValue>> {
+        let slice_offset = self.data_slice().offset;
+        let mut view = BTreeMap::new();
+
+        for field in self.fields.iter() {
+            let start = field.offset - slice_offset;
+            let bytes = sliced_data.get(start..start + field.field_type.size())?;
+            view.insert(field.name.to_string(), field.field_type.decode(bytes)?);
+        }
+
+        Some(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::anchor_lang::AccountSerialize;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new(&[seed; 32])
+    }
+
+    fn serialize<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    /// Checks that the layout's fields follow each other from the end of the discriminator to
+    /// the end of the data, and that each decodes to the value the struct was built with
+    fn check_layout(account_type: AccountType, data: &[u8], expected: &[(&str, FieldValue)]) {
+        assert_eq!(data[..8], account_type.discriminator());
+        assert_eq!(account_type.layout().len(), expected.len());
+
+        let mut end = 8;
+
+        for (field, (name, value)) in account_type.layout().iter().zip(expected.iter()) {
+            assert_eq!(field.name, *name);
+            assert_eq!(
+                field.offset, end,
+                "{} is not right after the previous field",
+                name
+            );
+
+            end = field.offset + field.field_type.size();
+            assert_eq!(
+                field.field_type.decode(&data[field.offset..end]).as_ref(),
+                Some(value),
+                "{} decoded to the wrong value",
+                name
+            );
+        }
+
+        assert_eq!(end, data.len());
+    }
+
+    fn sample_quarry() -> Quarry {
+        Quarry {
+            rewarder_key: key(1),
+            token_mint_key: key(2),
+            bump: 254,
+            index: 513,
+            token_mint_decimals: 9,
+            famine_ts: -1_640_995_200,
+            last_update_ts: 1_637_000_000,
+            rewards_per_token_stored: (1 << 100) + 3,
+            annual_rewards_rate: 5_000_000_000,
+            rewards_share: 400,
+            total_tokens_deposited: 123_456_789,
+            num_miners: 42,
+        }
+    }
+
+    #[test]
+    fn quarry_layout_matches_serialized_quarry() {
+        let quarry = sample_quarry();
+
+        check_layout(
+            AccountType::Quarry,
+            &serialize(&quarry),
+            &[
+                ("rewarder_key", FieldValue::Pubkey(quarry.rewarder_key)),
+                ("token_mint_key", FieldValue::Pubkey(quarry.token_mint_key)),
+                ("bump", FieldValue::Unsigned(254)),
+                ("index", FieldValue::Unsigned(513)),
+                ("token_mint_decimals", FieldValue::Unsigned(9)),
+                ("famine_ts", FieldValue::Signed(-1_640_995_200)),
+                ("last_update_ts", FieldValue::Signed(1_637_000_000)),
+                (
+                    "rewards_per_token_stored",
+                    FieldValue::Unsigned((1 << 100) + 3),
+                ),
+                ("annual_rewards_rate", FieldValue::Unsigned(5_000_000_000)),
+                ("rewards_share", FieldValue::Unsigned(400)),
+                ("total_tokens_deposited", FieldValue::Unsigned(123_456_789)),
+                ("num_miners", FieldValue::Unsigned(42)),
+            ],
+        );
+    }
+
+    #[test]
+    fn miner_layout_matches_serialized_miner() {
+        let miner = Miner {
+            quarry_key: key(3),
+            authority: key(4),
+            bump: 253,
+            token_vault_key: key(5),
+            rewards_earned: 77_000,
+            rewards_per_token_paid: u128::MAX - 1,
+            balance: 1_000_000,
+            index: 7,
+        };
+
+        check_layout(
+            AccountType::Miner,
+            &serialize(&miner),
+            &[
+                ("quarry_key", FieldValue::Pubkey(miner.quarry_key)),
+                ("authority", FieldValue::Pubkey(miner.authority)),
+                ("bump", FieldValue::Unsigned(253)),
+                ("token_vault_key", FieldValue::Pubkey(miner.token_vault_key)),
+                ("rewards_earned", FieldValue::Unsigned(77_000)),
+                (
+                    "rewards_per_token_paid",
+                    FieldValue::Unsigned(u128::MAX - 1),
+                ),
+                ("balance", FieldValue::Unsigned(1_000_000)),
+                ("index", FieldValue::Unsigned(7)),
+            ],
+        );
+    }
+
+    #[test]
+    fn rewarder_layout_matches_serialized_rewarder() {
+        let rewarder = Rewarder {
+            base: key(6),
+            bump: 252,
+            authority: key(7),
+            pending_authority: key(8),
+            num_quarries: 300,
+            annual_rewards_rate: 9_000_000_000,
+            total_rewards_shares: 1_000,
+            mint_wrapper: key(9),
+            rewards_token_mint: key(10),
+            claim_fee_token_account: key(11),
+            max_claim_fee_kbps: 100,
+            pause_authority: key(12),
+            is_paused: true,
+        };
+
+        check_layout(
+            AccountType::Rewarder,
+            &serialize(&rewarder),
+            &[
+                ("base", FieldValue::Pubkey(rewarder.base)),
+                ("bump", FieldValue::Unsigned(252)),
+                ("authority", FieldValue::Pubkey(rewarder.authority)),
+                (
+                    "pending_authority",
+                    FieldValue::Pubkey(rewarder.pending_authority),
+                ),
+                ("num_quarries", FieldValue::Unsigned(300)),
+                ("annual_rewards_rate", FieldValue::Unsigned(9_000_000_000)),
+                ("total_rewards_shares", FieldValue::Unsigned(1_000)),
+                ("mint_wrapper", FieldValue::Pubkey(rewarder.mint_wrapper)),
+                (
+                    "rewards_token_mint",
+                    FieldValue::Pubkey(rewarder.rewards_token_mint),
+                ),
+                (
+                    "claim_fee_token_account",
+                    FieldValue::Pubkey(rewarder.claim_fee_token_account),
+                ),
+                ("max_claim_fee_kbps", FieldValue::Unsigned(100)),
+                (
+                    "pause_authority",
+                    FieldValue::Pubkey(rewarder.pause_authority),
+                ),
+                ("is_paused", FieldValue::Bool(true)),
+            ],
+        );
+    }
+
+    #[test]
+    fn projection_decodes_its_data_slice() {
+        let quarry = sample_quarry();
+        let data = serialize(&quarry);
+        let names = vec!["famine_ts".to_string(), "token_mint_key".to_string()];
+        let projection = Projection::new(AccountType::Quarry, &names).unwrap();
+
+        let slice = projection.data_slice();
+        assert_eq!((slice.offset, slice.length), (40, 44));
+
+        let view = projection
+            .decode(&data[slice.offset..slice.offset + slice.length])
+            .unwrap();
+        assert_eq!(view.len(), 2);
+        assert_eq!(view["famine_ts"], FieldValue::Signed(quarry.famine_ts));
+        assert_eq!(
+            view["token_mint_key"],
+            FieldValue::Pubkey(quarry.token_mint_key)
+        );
+    }
+
+    #[test]
+    fn projection_rejects_unknown_fields() {
+        let names = vec!["balance".to_string(), "shares".to_string()];
+        assert_eq!(
+            Projection::new(AccountType::Miner, &names).unwrap_err(),
+            "shares"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use solana_program::keccak;
+use solana_program::pubkey::Pubkey;
+
+/// Computes a merkle-distributor leaf: keccak256(index || claimant || amount), with the
+/// integers encoded little endian
+pub fn merkle_leaf(index: u64, claimant: &Pubkey, amount: u64) -> [u8; 32] {
+    keccak::hashv(&[
+        &index.to_le_bytes(),
+        &claimant.to_bytes(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Hashes two nodes in sorted order so that proofs don't need to carry left/right flags
+fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    if a <= b {
+        keccak::hashv(&[a, b]).to_bytes()
+    } else {
+        keccak::hashv(&[b, a]).to_bytes()
+    }
+}
+
+/// Merkle tree over keccak256 leaves, built the same way as the merkle-distributor
+/// BalanceTree: leaves are sorted, pairs are hashed in sorted order and an odd node at the end
+/// of a layer is carried up unchanged
+pub struct MerkleTree {
+    layers: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: &[[u8; 32]]) -> Self {
+        let mut first = leaves.to_vec();
+        first.sort_unstable();
+        first.dedup();
+
+        let mut layers = vec![first];
+
+        while layers.last().map(|l| l.len() > 1).unwrap_or(false) {
+            let next = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+
+            layers.push(next);
+        }
+
+        Self { layers }
+    }
+
+    /// Root of the tree, or all zeroes for an empty tree
+    pub fn root(&self) -> [u8; 32] {
+        self.layers
+            .last()
+            .and_then(|l| l.first().copied())
+            .unwrap_or([0u8; 32])
+    }
+
+    /// Sibling hashes from the leaf up to (but excluding) the root. Returns None if the leaf
+    /// isn't part of the tree.
+    pub fn proof(&self, leaf: &[u8; 32]) -> Option<Vec<[u8; 32]>> {
+        let mut index = self.layers.first()?.binary_search(leaf).ok()?;
+        let mut proof = Vec::new();
+
+        for layer in self.layers.iter().take(self.layers.len().saturating_sub(1)) {
+            let sibling = if index % 2 == 0 { index + 1 } else { index - 1 };
+
+            if let Some(node) = layer.get(sibling) {
+                proof.push(*node);
+            }
+
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
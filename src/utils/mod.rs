@@ -1,9 +1,53 @@
+pub mod account_utils;
+pub mod activity_utils;
+pub mod cache_utils;
+pub mod coalesce_utils;
+pub mod consistent_utils;
+pub mod decode_utils;
+pub mod diff_utils;
+pub mod endpoint_utils;
+pub mod filter_utils;
+pub mod index_utils;
+pub mod instruction_utils;
+pub mod layout_utils;
+pub mod merkle_utils;
 pub mod network_utils;
+pub mod payroll_utils;
+pub mod pda_utils;
+pub mod price_utils;
 pub mod pubkey_utils;
+pub mod query_utils;
 pub mod request_utils;
 pub mod rpc_utils;
+pub mod stats_utils;
+pub mod subscription_utils;
+pub mod time_utils;
+pub mod transaction_utils;
+pub mod webhook_utils;
 
+pub use account_utils::*;
+pub use activity_utils::*;
+pub use cache_utils::*;
+pub use coalesce_utils::*;
+pub use consistent_utils::*;
+pub use decode_utils::*;
+pub use diff_utils::*;
+pub use endpoint_utils::*;
+pub use filter_utils::*;
+pub use index_utils::*;
+pub use instruction_utils::*;
+pub use layout_utils::*;
+pub use merkle_utils::*;
 pub use network_utils::*;
+pub use payroll_utils::*;
+pub use pda_utils::*;
+pub use price_utils::*;
 pub use pubkey_utils::*;
+pub use query_utils::*;
 pub use request_utils::*;
 pub use rpc_utils::*;
+pub use stats_utils::*;
+pub use subscription_utils::*;
+pub use time_utils::*;
+pub use transaction_utils::*;
+pub use webhook_utils::*;
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use solana_sdk::commitment_config::CommitmentConfig;
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Network {
     Mainnet,
     Devnet,
@@ -33,8 +34,9 @@ impl Network {
         }
     }
 
-    /// Takes in a Network variant and returns an rpc url
-    pub fn fetch_url(self) -> String {
+    /// Takes in a Network variant and returns the public rpc url used when no endpoints are
+    /// configured for it
+    pub fn default_url(self) -> String {
         match self {
             Self::Mainnet => "https://solana-api.projectserum.com".to_string(),
             Self::Devnet => "https://api.devnet.solana.com".to_string(),
@@ -42,3 +44,29 @@ impl Network {
         }
     }
 }
+
+/// Commitment level requests can read accounts at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Default for Commitment {
+    /// Matches the commitment every rpc_utils call uses
+    fn default() -> Self {
+        Self::Confirmed
+    }
+}
+
+impl Commitment {
+    /// Takes in a Commitment variant and returns the matching solana CommitmentConfig
+    pub fn fetch_config(self) -> CommitmentConfig {
+        match self {
+            Self::Processed => CommitmentConfig::processed(),
+            Self::Confirmed => CommitmentConfig::confirmed(),
+            Self::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{MinerWrapper, QuarryWrapper};
+use quarry_mine::payroll::{PRECISION_MULTIPLIER, SECONDS_PER_YEAR};
+use spl_math::uint::U192;
+use std::convert::TryFrom;
+
+/// Seconds the quarry has been emitting rewards for since its last checkpoint, capped at the
+/// famine timestamp
+pub fn compute_time_worked(quarry: &QuarryWrapper, current_ts: i64) -> i64 {
+    let last_time_reward_applicable = std::cmp::min(current_ts, quarry.famine_ts);
+    std::cmp::max(0, last_time_reward_applicable - quarry.last_update_ts)
+}
+
+/// Mirrors Payroll::calculate_reward_per_token_unsafe from quarry_mine. Returns the quarry's
+/// rewards_per_token value as if it had been checkpointed at `current_ts`.
+///
+/// The intermediate product overflows u128 after a few days for 9 decimal reward tokens, so
+/// like the program it is computed in U192.
+pub fn calculate_rewards_per_token(quarry: &QuarryWrapper, current_ts: i64) -> u128 {
+    if quarry.total_tokens_deposited == 0 {
+        return quarry.rewards_per_token_stored;
+    }
+
+    let time_worked = compute_time_worked(quarry, current_ts) as u64;
+    let reward = U192::from(time_worked)
+        * U192::from(PRECISION_MULTIPLIER)
+        * U192::from(quarry.annual_rewards_rate)
+        / U192::from(SECONDS_PER_YEAR)
+        / U192::from(quarry.total_tokens_deposited);
+
+    quarry
+        .rewards_per_token_stored
+        .saturating_add(u128::try_from(reward).unwrap_or(u128::MAX))
+}
+
+/// Mirrors Payroll::calculate_rewards_earned from quarry_mine. Returns the total amount of
+/// rewards the miner could claim at `current_ts`, including rewards already checkpointed
+/// into Miner::rewards_earned.
+pub fn calculate_rewards_earned(
+    quarry: &QuarryWrapper,
+    miner: &MinerWrapper,
+    current_ts: i64,
+) -> u128 {
+    let rewards_per_token = calculate_rewards_per_token(quarry, current_ts);
+    let net_new_rewards = rewards_per_token.saturating_sub(miner.rewards_per_token_paid);
+    let earned =
+        U192::from(miner.balance) * U192::from(net_new_rewards) / U192::from(PRECISION_MULTIPLIER);
+
+    u128::try_from(earned)
+        .unwrap_or(u128::MAX)
+        .saturating_add(miner.rewards_earned as u128)
+}
+
+/// Annual rate the quarry actually emits at `ts`: zero once famine has started or while
+/// nothing is staked, since rewards_per_token only grows with a non zero deposit
+pub fn effective_rewards_rate(quarry: &QuarryWrapper, ts: i64) -> u64 {
+    if quarry.famine_ts <= ts || quarry.total_tokens_deposited == 0 {
+        0
+    } else {
+        quarry.annual_rewards_rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quarry_mine::payroll::Payroll;
+    use solana_program::pubkey::Pubkey;
+
+    const DAY: i64 = 86_400;
+
+    fn quarry(annual_rewards_rate: u64, total_tokens_deposited: u64) -> QuarryWrapper {
+        QuarryWrapper {
+            rewarder_key: Pubkey::default(),
+            token_mint_key: Pubkey::default(),
+            bump: 255,
+            index: 0,
+            token_mint_decimals: 6,
+            famine_ts: i64::MAX,
+            last_update_ts: 0,
+            rewards_per_token_stored: 0,
+            annual_rewards_rate,
+            rewards_share: 1,
+            total_tokens_deposited,
+            num_miners: 1,
+        }
+    }
+
+    fn miner(balance: u64, rewards_per_token_paid: u128, rewards_earned: u64) -> MinerWrapper {
+        MinerWrapper {
+            quarry_key: Pubkey::default(),
+            authority: Pubkey::default(),
+            bump: 255,
+            token_vault_key: Pubkey::default(),
+            rewards_earned,
+            rewards_per_token_paid,
+            balance,
+            index: 0,
+        }
+    }
+
+    /// The program's own calculator for the quarry, the source of every expected value below
+    fn payroll(quarry: &QuarryWrapper) -> Payroll {
+        Payroll::new(
+            quarry.famine_ts,
+            quarry.last_update_ts,
+            quarry.annual_rewards_rate,
+            quarry.rewards_per_token_stored,
+            quarry.total_tokens_deposited,
+        )
+    }
+
+    /// A quarry checkpointed partway through its life, with rewards already stored
+    fn checkpointed_quarry(annual_rewards_rate: u64, total_tokens_deposited: u64) -> QuarryWrapper {
+        let mut quarry = quarry(annual_rewards_rate, total_tokens_deposited);
+        quarry.last_update_ts = 1_637_000_000;
+        quarry.famine_ts = 1_637_000_000 + 90 * DAY;
+        quarry.rewards_per_token_stored = 37 * PRECISION_MULTIPLIER + 12_345;
+        quarry
+    }
+
+    #[test]
+    fn rewards_per_token_matches_quarry_mine() {
+        let quarries = [
+            quarry(1_000_000, 1_000),
+            checkpointed_quarry(1_000_000, 1_000),
+            checkpointed_quarry(3_000_000_000_000, 7_777_777),
+            // 9 decimal rates overflow u128 in the intermediate product after a few days
+            checkpointed_quarry(1_000_000_000_000_000_000, 1_000_000_000_000),
+        ];
+
+        for quarry in quarries.iter() {
+            for elapsed in [0, 1, DAY, 5 * DAY, 89 * DAY, 365 * DAY].iter() {
+                let ts = quarry.last_update_ts + elapsed;
+                assert_eq!(
+                    calculate_rewards_per_token(quarry, ts),
+                    payroll(quarry).calculate_reward_per_token(ts).unwrap(),
+                    "{} seconds after the checkpoint",
+                    elapsed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rewards_earned_matches_quarry_mine() {
+        let quarry = checkpointed_quarry(3_000_000_000_000, 7_777_777);
+        let miners = [
+            miner(1, 0, 0),
+            miner(500, quarry.rewards_per_token_stored, 0),
+            miner(1_234_567, 30 * PRECISION_MULTIPLIER + 999, 42),
+            miner(7_777_777, 36 * PRECISION_MULTIPLIER, 1_000_000),
+        ];
+
+        for miner in miners.iter() {
+            for elapsed in [0, DAY, 30 * DAY, 365 * DAY].iter() {
+                let ts = quarry.last_update_ts + elapsed;
+                let expected = payroll(&quarry)
+                    .calculate_rewards_earned(
+                        ts,
+                        miner.balance,
+                        miner.rewards_per_token_paid,
+                        miner.rewards_earned,
+                    )
+                    .unwrap();
+
+                assert_eq!(calculate_rewards_earned(&quarry, miner, ts), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn rewards_per_token_stops_at_famine() {
+        let quarry = checkpointed_quarry(1_000_000, 1_000);
+        assert_eq!(
+            calculate_rewards_per_token(&quarry, quarry.famine_ts + 365 * DAY),
+            calculate_rewards_per_token(&quarry, quarry.famine_ts)
+        );
+    }
+
+    #[test]
+    fn rewards_per_token_unchanged_without_deposits() {
+        let mut quarry = quarry(1_000_000, 0);
+        quarry.rewards_per_token_stored = 42;
+        assert_eq!(calculate_rewards_per_token(&quarry, 365 * DAY), 42);
+    }
+}
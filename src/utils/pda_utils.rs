@@ -0,0 +1,41 @@
+use solana_program::pubkey::Pubkey;
+
+/// Derives the Rewarder address (and bump) for the given base key
+pub fn find_rewarder_address(base: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"Rewarder", base.as_ref()], &quarry_mine::ID)
+}
+
+/// Derives the Quarry address (and bump) for the given rewarder and staked token mint
+pub fn find_quarry_address(rewarder: &Pubkey, token_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"Quarry", rewarder.as_ref(), token_mint.as_ref()],
+        &quarry_mine::ID,
+    )
+}
+
+/// Derives the Miner address (and bump) for the given quarry and miner authority
+pub fn find_miner_address(quarry: &Pubkey, authority: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"Miner", quarry.as_ref(), authority.as_ref()],
+        &quarry_mine::ID,
+    )
+}
+
+/// Derives the Miner's token vault, which the Quarry SDK creates as the associated token
+/// account of the Miner for the quarry's staked token mint
+pub fn find_miner_vault_address(miner: &Pubkey, token_mint: &Pubkey) -> Pubkey {
+    spl_associated_token_account::get_associated_token_address(miner, token_mint)
+}
+
+/// Derives the mint wrapper Minter address (and bump) for the given minter authority. For a
+/// rewarder, the minter authority is the rewarder itself.
+pub fn find_minter_address(mint_wrapper: &Pubkey, minter_authority: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"MintWrapperMinter",
+            mint_wrapper.as_ref(),
+            minter_authority.as_ref(),
+        ],
+        &quarry_mint_wrapper::ID,
+    )
+}
@@ -0,0 +1,94 @@
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::QuarryWrapper;
+
+/// Oldest a price may be, relative to the point it's used for, before it's treated as missing
+pub const PRICE_MAX_AGE: i64 = 86_400;
+
+/// Location of the price history file, read fresh on every request so an external job can
+/// keep appending to it
+pub struct PriceSource {
+    pub path: PathBuf,
+}
+
+impl PriceSource {
+    pub fn load(&self) -> io::Result<PriceHistory> {
+        fs::read_to_string(&self.path).map(|contents| PriceHistory::parse(&contents))
+    }
+}
+
+/// USD prices per token mint over time
+#[derive(Default)]
+pub struct PriceHistory {
+    prices: HashMap<Pubkey, Vec<(i64, f64)>>,
+}
+
+impl PriceHistory {
+    /// Parses "mint,timestamp,price" CSV lines, where mint is base58, timestamp is unix seconds
+    /// and price is the USD price of one whole token. A header line and malformed lines are
+    /// skipped.
+    pub fn parse(contents: &str) -> Self {
+        let mut prices: HashMap<Pubkey, Vec<(i64, f64)>> = HashMap::new();
+
+        for line in contents.lines() {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+
+            if let [mint, ts, price] = fields.as_slice() {
+                if let (Ok(mint), Ok(ts), Ok(price)) = (
+                    Pubkey::from_str(mint),
+                    ts.parse::<i64>(),
+                    price.parse::<f64>(),
+                ) {
+                    prices.entry(mint).or_default().push((ts, price));
+                }
+            }
+        }
+
+        for series in prices.values_mut() {
+            series.sort_by_key(|(ts, _)| *ts);
+        }
+
+        Self { prices }
+    }
+
+    /// Latest price of the mint at or before `ts`, unless it's older than PRICE_MAX_AGE
+    pub fn price_at(&self, mint: &Pubkey, ts: i64) -> Option<f64> {
+        let series = self.prices.get(mint)?;
+        let i = series.partition_point(|(t, _)| *t <= ts);
+        let (price_ts, price) = series.get(i.checked_sub(1)?)?;
+
+        if ts - price_ts > PRICE_MAX_AGE {
+            None
+        } else {
+            Some(*price)
+        }
+    }
+}
+
+/// Annual rewards value over staked value, e.g. 0.25 for 25%. None when nothing is staked or
+/// the staked value is zero.
+pub fn quarry_apr(
+    quarry: &QuarryWrapper,
+    annual_rewards_rate: u64,
+    staked_price: f64,
+    rewards_price: f64,
+    rewards_decimals: u8,
+) -> Option<f64> {
+    let staked_value = quarry.total_tokens_deposited as f64
+        / 10f64.powi(quarry.token_mint_decimals as i32)
+        * staked_price;
+
+    if staked_value <= 0.0 {
+        return None;
+    }
+
+    let rewards_value =
+        annual_rewards_rate as f64 / 10f64.powi(rewards_decimals as i32) * rewards_price;
+
+    Some(rewards_value / staked_value)
+}
@@ -0,0 +1,551 @@
+use crate::utils::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Number of accounts returned when the query doesn't specify a limit
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+
+/// Most accounts a single query page can return
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// How a predicate compares a field against its value
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum QueryOperator {
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanOrEquals,
+    GreaterThan,
+    GreaterThanOrEquals,
+}
+
+impl QueryOperator {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Equals => ordering == Ordering::Equal,
+            Self::NotEquals => ordering != Ordering::Equal,
+            Self::LessThan => ordering == Ordering::Less,
+            Self::LessThanOrEquals => ordering != Ordering::Greater,
+            Self::GreaterThan => ordering == Ordering::Greater,
+            Self::GreaterThanOrEquals => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A predicate on a field of the account wrapper
+///
+/// {
+///     "field": "famine_ts", ~ wrapper field name, e.g. balance, authority, is_paused
+///     "operator": "LessThan",
+///     "value": "now" ~ "now" is the current unix timestamp, numbers may use exponents (1e9)
+/// }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryPredicate {
+    pub field: String,
+    pub operator: QueryOperator,
+    pub value: Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// Order of the results. Ties, and queries without a sort, are ordered by account pubkey.
+///
+/// {
+///     "field": "balance",
+///     "direction": "Descending"
+/// }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QuerySort {
+    pub field: String,
+    pub direction: SortDirection,
+}
+
+/// Position of the last account of a page, results continue strictly after it
+#[derive(Clone, Debug)]
+pub struct QueryCursor {
+    pub value: Option<FieldValue>,
+    pub pubkey: Pubkey,
+}
+
+/// A decoded account, holding only the fields the query returns
+#[derive(Serialize, Deserialize)]
+pub struct QueryEntry {
+    pub pubkey: Pubkey,
+    pub account: BTreeMap<String, FieldValue>,
+}
+
+/// One page of query results
+pub struct QueryPage {
+    pub accounts: Vec<QueryEntry>,
+    /// Accounts matching every predicate, across all pages
+    pub total_matches: u64,
+    /// Pass as "cursor" to fetch the next page, None on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// A query validated against the account layout. Equality predicates are pushed down to the
+/// RPC node as Memcmp filters, the rest are evaluated on decoded accounts.
+pub struct AccountQuery {
+    account_type: AccountType,
+    predicates: Vec<(&'static FieldLayout, QueryOperator, FieldValue)>,
+    sort: Option<(&'static FieldLayout, SortDirection)>,
+    output: Vec<&'static FieldLayout>,
+}
+
+impl AccountQuery {
+    /// Resolves field names and predicate values, failing with a message describing the first
+    /// invalid part of the query. `fields` defaults to every field of the account type.
+    pub fn new(
+        account_type: AccountType,
+        predicates: &[QueryPredicate],
+        sort: Option<&QuerySort>,
+        fields: Option<&[String]>,
+        now: i64,
+    ) -> Result<Self, String> {
+        let lookup = |name: &str| {
+            account_type
+                .field(name)
+                .ok_or_else(|| format!("Unknown {:?} field: {}", account_type, name))
+        };
+
+        let mut resolved = Vec::with_capacity(predicates.len());
+
+        for predicate in predicates.iter() {
+            let layout = lookup(&predicate.field)?;
+            let value =
+                resolve_value(layout.field_type, &predicate.value, now).ok_or_else(|| {
+                    format!(
+                        "Invalid value for {:?} field: {}",
+                        account_type, predicate.field
+                    )
+                })?;
+
+            resolved.push((layout, predicate.operator, value));
+        }
+
+        let sort = match sort {
+            Some(sort) => Some((lookup(&sort.field)?, sort.direction)),
+            None => None,
+        };
+
+        let output = match fields {
+            Some(names) => names
+                .iter()
+                .map(|name| lookup(name))
+                .collect::<Result<Vec<_>, String>>()?,
+            None => account_type.layout().iter().collect(),
+        };
+
+        Ok(Self {
+            account_type,
+            predicates: resolved,
+            sort,
+            output,
+        })
+    }
+
+    /// Memcmp filters for the discriminator and every Equals predicate
+    pub fn memcmp_filters(&self) -> Result<AccountFilters, String> {
+        let mut filters = AccountFilters::new(self.account_type);
+
+        for (layout, operator, value) in self.predicates.iter() {
+            if *operator == QueryOperator::Equals {
+                filters = filters.equals(layout.name, value)?;
+            }
+        }
+
+        Ok(filters)
+    }
+
+    /// Projection covering every field the query reads or returns
+    pub fn projection(&self) -> Projection {
+        let mut fields: Vec<&'static FieldLayout> = self.output.clone();
+        fields.extend(self.predicates.iter().map(|(layout, _, _)| *layout));
+        fields.extend(self.sort.iter().map(|(layout, _)| *layout));
+
+        fields.sort_by_key(|f| f.offset);
+        fields.dedup_by_key(|f| f.offset);
+
+        Projection { fields }
+    }
+
+    /// Filters, sorts and pages accounts scanned with `projection()`
+    pub fn execute(
+        &self,
+        accounts: &[(Pubkey, Account)],
+        cursor: Option<&QueryCursor>,
+        limit: usize,
+    ) -> Result<QueryPage, String> {
+        let projection = self.projection();
+        let mut matches = Vec::new();
+
+        for (pubkey, account) in accounts.iter() {
+            let view = projection
+                .decode(&account.data)
+                .ok_or_else(|| format!("Failed to decode {:?} {}", self.account_type, pubkey))?;
+
+            if self.matches(&view) {
+                matches.push((*pubkey, view));
+            }
+        }
+
+        matches.sort_by(|a, b| self.compare_keys(&self.key(a), &self.key(b)));
+
+        let total_matches = matches.len() as u64;
+        let start = match cursor {
+            Some(cursor) => matches
+                .iter()
+                .position(|m| self.compare_keys(&self.key(m), cursor) == Ordering::Greater)
+                .unwrap_or(matches.len()),
+            None => 0,
+        };
+
+        let end = std::cmp::min(start + limit, matches.len());
+        let next_cursor = if end < matches.len() && end > start {
+            Some(self.format_cursor(&self.key(&matches[end - 1])))
+        } else {
+            None
+        };
+
+        let accounts = matches
+            .drain(start..end)
+            .map(|(pubkey, mut view)| {
+                view.retain(|name, _| self.output.iter().any(|f| f.name == name));
+                QueryEntry {
+                    pubkey,
+                    account: view,
+                }
+            })
+            .collect();
+
+        Ok(QueryPage {
+            accounts,
+            total_matches,
+            next_cursor,
+        })
+    }
+
+    fn matches(&self, view: &BTreeMap<String, FieldValue>) -> bool {
+        self.predicates.iter().all(|(layout, operator, value)| {
+            view.get(layout.name)
+                .and_then(|field| field.compare(value))
+                .map_or(false, |ordering| operator.holds(ordering))
+        })
+    }
+
+    /// Sort value and pubkey of a match, the position a cursor points at
+    fn key(&self, entry: &(Pubkey, BTreeMap<String, FieldValue>)) -> QueryCursor {
+        QueryCursor {
+            value: self
+                .sort
+                .and_then(|(layout, _)| entry.1.get(layout.name).cloned()),
+            pubkey: entry.0,
+        }
+    }
+
+    fn compare_keys(&self, a: &QueryCursor, b: &QueryCursor) -> Ordering {
+        let by_value = match (&a.value, &b.value) {
+            (Some(x), Some(y)) => x.compare(y).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        };
+        let ordering = by_value.then_with(|| a.pubkey.to_bytes().cmp(&b.pubkey.to_bytes()));
+
+        match self.sort {
+            Some((_, SortDirection::Descending)) => ordering.reverse(),
+            _ => ordering,
+        }
+    }
+
+    /// Cursors are "<sort value>:<base58 pubkey>", or just the pubkey without a sort
+    fn format_cursor(&self, key: &QueryCursor) -> String {
+        match &key.value {
+            Some(FieldValue::Pubkey(value)) => format!("{}:{}", value, key.pubkey),
+            Some(FieldValue::Bool(value)) => format!("{}:{}", value, key.pubkey),
+            Some(FieldValue::Unsigned(value)) => format!("{}:{}", value, key.pubkey),
+            Some(FieldValue::Signed(value)) => format!("{}:{}", value, key.pubkey),
+            None => key.pubkey.to_string(),
+        }
+    }
+
+    /// Reads a next_cursor returned by a previous page of the same query
+    pub fn parse_cursor(&self, cursor: &str) -> Result<QueryCursor, String> {
+        let invalid = || format!("Invalid cursor: {}", cursor);
+
+        let (value, pubkey) = match (self.sort, cursor.rsplit_once(':')) {
+            (Some((layout, _)), Some((value, pubkey))) => {
+                let value = match layout.field_type {
+                    FieldType::Bool => value.parse::<bool>().ok().map(FieldValue::Bool),
+                    field_type => field_type.parse(&Value::String(value.to_string())),
+                };
+                (Some(value.ok_or_else(invalid)?), pubkey)
+            }
+            (None, None) => (None, cursor),
+            _ => return Err(invalid()),
+        };
+
+        Ok(QueryCursor {
+            value,
+            pubkey: Pubkey::from_str(pubkey).map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Reads a predicate value as the field's type, accepting "now" for timestamps and integral
+/// floats such as 1e9 for integers
+fn resolve_value(field_type: FieldType, value: &Value, now: i64) -> Option<FieldValue> {
+    match (field_type, value) {
+        (FieldType::I64, Value::String(s)) if s == "now" => Some(FieldValue::Signed(now)),
+        (FieldType::I64, Value::Number(n)) if n.as_i64().is_none() => {
+            let f = n.as_f64()?;
+            (f.fract() == 0.0 && f.abs() <= i64::MAX as f64).then(|| FieldValue::Signed(f as i64))
+        }
+        (FieldType::U8, Value::Number(n))
+        | (FieldType::U16, Value::Number(n))
+        | (FieldType::U64, Value::Number(n))
+        | (FieldType::U128, Value::Number(n))
+            if n.as_u64().is_none() =>
+        {
+            let f = n.as_f64()?;
+            (f.fract() == 0.0 && f >= 0.0).then(|| FieldValue::Unsigned(f as u128))
+        }
+        _ => field_type.parse(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::anchor_lang::AccountSerialize;
+    use quarry_mine::Miner;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    fn key(seed: u8) -> Pubkey {
+        Pubkey::new(&[seed; 32])
+    }
+
+    fn sorted_by(account_type: AccountType, field: &str) -> AccountQuery {
+        let sort = QuerySort {
+            field: field.to_string(),
+            direction: SortDirection::Ascending,
+        };
+
+        AccountQuery::new(account_type, &[], Some(&sort), None, 0).unwrap()
+    }
+
+    fn miner_account(seed: u8, balance: u64) -> (Pubkey, Account) {
+        let miner = Miner {
+            quarry_key: key(1),
+            authority: key(seed),
+            bump: 255,
+            token_vault_key: key(2),
+            rewards_earned: 0,
+            rewards_per_token_paid: 0,
+            balance,
+            index: seed as u64,
+        };
+
+        let mut data = Vec::new();
+        miner.try_serialize(&mut data).unwrap();
+
+        (
+            key(seed),
+            Account {
+                lamports: 1,
+                data,
+                owner: Pubkey::default(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )
+    }
+
+    /// Cuts each account's data down to the query's data slice, as the RPC node returns it
+    fn sliced(query: &AccountQuery, accounts: &[(Pubkey, Account)]) -> Vec<(Pubkey, Account)> {
+        let slice = query.projection().data_slice();
+
+        accounts
+            .iter()
+            .map(|(pubkey, account)| {
+                let mut account = account.clone();
+                account.data = account.data[slice.offset..slice.offset + slice.length].to_vec();
+                (*pubkey, account)
+            })
+            .collect()
+    }
+
+    /// Pages through the whole query, following next_cursor through its string form
+    fn collect_pages(
+        query: &AccountQuery,
+        accounts: &[(Pubkey, Account)],
+        limit: usize,
+    ) -> Vec<Pubkey> {
+        let mut seen = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = query.execute(accounts, cursor.as_ref(), limit).unwrap();
+            assert!(page.accounts.len() <= limit);
+            seen.extend(page.accounts.iter().map(|entry| entry.pubkey));
+
+            match page.next_cursor {
+                Some(next) => cursor = Some(query.parse_cursor(&next).unwrap()),
+                None => return seen,
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips_for_every_field_type() {
+        let pubkey = key(9);
+        let cases = [
+            (
+                AccountType::Quarry,
+                "token_mint_key",
+                FieldValue::Pubkey(key(2)),
+            ),
+            (AccountType::Rewarder, "is_paused", FieldValue::Bool(true)),
+            (AccountType::Quarry, "bump", FieldValue::Unsigned(255)),
+            (AccountType::Quarry, "index", FieldValue::Unsigned(65_535)),
+            (
+                AccountType::Quarry,
+                "num_miners",
+                FieldValue::Unsigned(u64::MAX as u128),
+            ),
+            (
+                AccountType::Quarry,
+                "famine_ts",
+                FieldValue::Signed(-1_640_995_200),
+            ),
+            (
+                AccountType::Quarry,
+                "famine_ts",
+                FieldValue::Signed(i64::MIN),
+            ),
+            (
+                AccountType::Quarry,
+                "rewards_per_token_stored",
+                FieldValue::Unsigned(u128::MAX),
+            ),
+        ];
+
+        for (account_type, field, value) in cases.iter() {
+            let query = sorted_by(*account_type, field);
+            let cursor = query.format_cursor(&QueryCursor {
+                value: Some(value.clone()),
+                pubkey,
+            });
+            let parsed = query.parse_cursor(&cursor).unwrap();
+
+            assert_eq!(parsed.value.as_ref(), Some(value), "{}", cursor);
+            assert_eq!(parsed.pubkey, pubkey, "{}", cursor);
+        }
+    }
+
+    #[test]
+    fn cursor_without_sort_is_the_pubkey() {
+        let query = AccountQuery::new(AccountType::Miner, &[], None, None, 0).unwrap();
+        let cursor = query.format_cursor(&QueryCursor {
+            value: None,
+            pubkey: key(3),
+        });
+
+        assert_eq!(cursor, key(3).to_string());
+        assert_eq!(query.parse_cursor(&cursor).unwrap().pubkey, key(3));
+        assert!(query.parse_cursor(&format!("1:{}", key(3))).is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_values_of_the_wrong_type() {
+        let query = sorted_by(AccountType::Miner, "balance");
+
+        assert!(query.parse_cursor(&format!("-5:{}", key(3))).is_err());
+        assert!(query.parse_cursor(&key(3).to_string()).is_err());
+    }
+
+    #[test]
+    fn pages_cover_every_match_once_in_order() {
+        // Repeated balances make the pubkey tie-break decide page boundaries
+        let accounts: Vec<(Pubkey, Account)> = (10..33)
+            .map(|seed| miner_account(seed, (seed as u64 % 4) * 1_000))
+            .collect();
+
+        for direction in [SortDirection::Ascending, SortDirection::Descending].iter() {
+            let sort = QuerySort {
+                field: "balance".to_string(),
+                direction: *direction,
+            };
+            let query = AccountQuery::new(AccountType::Miner, &[], Some(&sort), None, 0).unwrap();
+            let accounts = sliced(&query, &accounts);
+            let everything = query.execute(&accounts, None, MAX_QUERY_LIMIT).unwrap();
+            let expected: Vec<Pubkey> = everything.accounts.iter().map(|e| e.pubkey).collect();
+
+            assert!(everything.next_cursor.is_none());
+            assert_eq!(expected.len(), accounts.len());
+            assert!(everything.accounts.windows(2).all(|pair| {
+                let ordering = pair[0].account["balance"].compare(&pair[1].account["balance"]);
+                match direction {
+                    SortDirection::Ascending => ordering != Some(Ordering::Greater),
+                    SortDirection::Descending => ordering != Some(Ordering::Less),
+                }
+            }));
+
+            for limit in 1..=accounts.len() {
+                let seen = collect_pages(&query, &accounts, limit);
+                let unique: HashSet<Pubkey> = seen.iter().copied().collect();
+
+                assert_eq!(unique.len(), seen.len(), "duplicates with limit {}", limit);
+                assert_eq!(seen, expected, "gaps or reordering with limit {}", limit);
+            }
+        }
+    }
+
+    #[test]
+    fn predicates_filter_before_paging() {
+        let accounts: Vec<(Pubkey, Account)> = (10..20)
+            .map(|seed| miner_account(seed, seed as u64 * 100))
+            .collect();
+        let predicates = [QueryPredicate {
+            field: "balance".to_string(),
+            operator: QueryOperator::GreaterThanOrEquals,
+            value: json!(1.5e3),
+        }];
+        let fields = ["balance".to_string()];
+        let query =
+            AccountQuery::new(AccountType::Miner, &predicates, None, Some(&fields), 0).unwrap();
+
+        let accounts = sliced(&query, &accounts);
+        let page = query.execute(&accounts, None, 2).unwrap();
+
+        assert_eq!(page.total_matches, 5);
+        assert_eq!(page.accounts.len(), 2);
+        assert!(page.next_cursor.is_some());
+        assert!(page
+            .accounts
+            .iter()
+            .all(|entry| entry.account.keys().collect::<Vec<_>>() == vec!["balance"]));
+        assert_eq!(collect_pages(&query, &accounts, 2).len(), 5);
+    }
+
+    #[test]
+    fn now_resolves_to_the_current_timestamp() {
+        let predicates = [QueryPredicate {
+            field: "famine_ts".to_string(),
+            operator: QueryOperator::LessThan,
+            value: json!("now"),
+        }];
+        let query =
+            AccountQuery::new(AccountType::Quarry, &predicates, None, None, 1_700_000_000).unwrap();
+
+        assert_eq!(query.predicates[0].2, FieldValue::Signed(1_700_000_000));
+    }
+}
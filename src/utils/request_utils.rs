@@ -1,4 +1,7 @@
-use crate::utils::{network_utils::NetworkConfig, pubkey_utils::PubkeyConfig};
+use crate::utils::{
+    network_utils::{Commitment, NetworkConfig},
+    pubkey_utils::PubkeyConfig,
+};
 use serde::{Deserialize, Serialize};
 
 /// Example request
@@ -16,3 +19,21 @@ pub struct FetchAccountRequest {
     pub network_config: NetworkConfig,
     pub pubkey_config: PubkeyConfig,
 }
+
+/// Example request
+///
+/// {
+///     "network_config": {
+///         "variant": "Mainnet"
+///     },
+///     "pubkey_config": {
+///         "pubkey": [...] ~ an array of 32 unsigned 8-bit integers
+///     },
+///     "commitment": "Finalized" ~ optional, "Processed", "Confirmed" (default) or "Finalized"
+/// }
+#[derive(Serialize, Deserialize)]
+pub struct FetchCachedAccountRequest {
+    pub network_config: NetworkConfig,
+    pub pubkey_config: PubkeyConfig,
+    pub commitment: Option<Commitment>,
+}
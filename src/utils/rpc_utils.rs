@@ -1,57 +1,105 @@
-use anchor_client::anchor_lang::AccountDeserialize;
-use quarry_mine::{Miner, Quarry, Rewarder};
-use solana_account_decoder::UiAccountEncoding;
-use solana_client::client_error::ClientError;
-use solana_client::rpc_client::RpcClient;
-use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
-use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use once_cell::sync::Lazy;
+use serde_json::json;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionAccountsConfig,
+    RpcSimulateTransactionConfig,
+};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{
+    Response as RpcResponse, RpcConfirmedTransactionStatusWithSignature, RpcKeyedAccount,
+    RpcSimulateTransactionResult,
+};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::account::Account;
 use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
-use solana_sdk::program_error::ProgramError;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::{EncodedConfirmedTransaction, UiTransactionEncoding};
+use std::str::FromStr;
 
 use crate::utils::*;
 
-/// Fetch all quarries for any LP token. Returns a vector of Pubkeys.
-// pub fn fetch_quarries_for_lp_token(network: &Network, lp_token_mint: &Pubkey) -> Vec<Pubkey> {
-//     let quarry_mine_pubkey = Pubkey::from_str(QUARRY_MINE_PUBKEY).unwrap();
+/// Concurrent identical account and program account fetches share one upstream request
+static ACCOUNT_FLIGHTS: Lazy<SingleFlight<RpcCallKey, Account>> = Lazy::new(SingleFlight::new);
+static ACCOUNT_WITH_SLOT_FLIGHTS: Lazy<SingleFlight<RpcCallKey, (Account, u64)>> =
+    Lazy::new(SingleFlight::new);
+static PROGRAM_ACCOUNTS_FLIGHTS: Lazy<SingleFlight<RpcCallKey, Vec<(Pubkey, Account)>>> =
+    Lazy::new(SingleFlight::new);
+static PROGRAM_ACCOUNTS_WITH_SLOT_FLIGHTS: Lazy<
+    SingleFlight<RpcCallKey, (Vec<(Pubkey, Account)>, u64)>,
+> = Lazy::new(SingleFlight::new);
 
-//     let token_mint_filter = Memcmp {
-//         offset: 8 + 32,
-//         bytes: MemcmpEncodedBytes::Bytes(lp_token_mint.to_bytes().into()),
-//         encoding: None,
-//     };
+/// Fetches the current blockheight
+pub fn fetch_blockheight(network: &Network) -> Result<u64, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_block_height()
+    })
+}
 
-//     let conf: RpcProgramAccountsConfig = RpcProgramAccountsConfig {
-//         filters: Some(vec![RpcFilterType::Memcmp(token_mint_filter)]),
-//         account_config: RpcAccountInfoConfig::default(),
-//         with_context: None,
-//     };
+/// Fetches the requested account from the specified network and pubkey
+pub fn fetch_account(network: &Network, account_pubkey: &Pubkey) -> Result<Account, ClientError> {
+    let key = RpcCallKey::Account {
+        network: *network,
+        pubkey: account_pubkey.to_string(),
+        commitment: format!("{:?}", CommitmentLevel::Confirmed),
+    };
 
-//     let rpc = RpcClient::new_with_commitment(network.fetch_url(), CommitmentConfig::confirmed());
-//     let res = rpc
-//         .get_program_accounts_with_config(&quarry_mine_pubkey, conf)
-//         .unwrap();
+    ACCOUNT_FLIGHTS.run(key, || {
+        with_failover(network, |url| {
+            let rpc =
+                RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+            rpc.get_account(account_pubkey)
+        })
+    })
+}
 
-//     let mut pubkey_vec = vec![];
+/// Fetches the requested account, returning None instead of an error when it doesn't exist so
+/// callers can tell a missing account apart from a failed request
+pub fn fetch_account_if_exists(
+    network: &Network,
+    account_pubkey: &Pubkey,
+) -> Result<Option<Account>, ClientError> {
+    let response = with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_account_with_commitment(account_pubkey, CommitmentConfig::confirmed())
+    })?;
 
-//     for item in res.iter() {
-//         pubkey_vec.push(item.0)
-//     }
+    Ok(response.value)
+}
 
-//     pubkey_vec
-// }
+/// Fetches the requested account at the given commitment along with the slot it was read at
+pub fn fetch_account_with_slot(
+    network: &Network,
+    account_pubkey: &Pubkey,
+    commitment: Commitment,
+) -> Result<(Account, u64), ClientError> {
+    let key = RpcCallKey::Account {
+        network: *network,
+        pubkey: account_pubkey.to_string(),
+        commitment: format!("{:?}", commitment),
+    };
 
-/// Fetches the current blockheight
-pub fn fetch_blockheight(network: &Network) -> Result<u64, ClientError> {
-    let rpc = RpcClient::new_with_commitment(network.fetch_url(), CommitmentConfig::confirmed());
-    rpc.get_block_height()
-}
+    ACCOUNT_WITH_SLOT_FLIGHTS.run(key, || {
+        let response = with_failover(network, |url| {
+            let rpc = RpcClient::new_with_commitment(url.to_string(), commitment.fetch_config());
+            rpc.get_account_with_commitment(account_pubkey, commitment.fetch_config())
+        })?;
 
-/// Fetches the requested account from the specified network and pubkey
-pub fn fetch_account(network: &Network, account_pubkey: &Pubkey) -> Result<Account, ClientError> {
-    let rpc = RpcClient::new_with_commitment(network.fetch_url(), CommitmentConfig::confirmed());
-    rpc.get_account(account_pubkey)
+        match response.value {
+            Some(account) => Ok((account, response.context.slot)),
+            None => Err(ClientErrorKind::Custom(format!(
+                "AccountNotFound: pubkey={}",
+                account_pubkey
+            ))
+            .into()),
+        }
+    })
 }
 
 /// Fetches all program accounts and optionally allows for the passing of Memcmp filters.
@@ -65,12 +113,118 @@ pub fn fetch_program_accounts(
     filters: Option<Vec<Memcmp>>,
     commitment: Option<CommitmentLevel>,
 ) -> Result<Vec<(Pubkey, Account)>, ClientError> {
-    let rpc = RpcClient::new_with_commitment(network.fetch_url(), CommitmentConfig::confirmed());
+    fetch_program_accounts_sliced(network, program_id, filters, commitment, None)
+}
+
+/// Same as fetch_program_accounts, but only downloads the `data_slice` byte range of each
+/// account when one is given. Account data then holds just that range.
+pub fn fetch_program_accounts_sliced(
+    network: &Network,
+    program_id: &Pubkey,
+    filters: Option<Vec<Memcmp>>,
+    commitment: Option<CommitmentLevel>,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<Vec<(Pubkey, Account)>, ClientError> {
+    let key = RpcCallKey::ProgramAccounts {
+        network: *network,
+        program_id: program_id.to_string(),
+        filters: format!("{:?}", filters),
+        commitment: format!("{:?}", commitment),
+        data_slice: format!("{:?}", data_slice),
+    };
+
+    PROGRAM_ACCOUNTS_FLIGHTS.run(key, || {
+        fetch_program_accounts_uncoalesced(network, program_id, filters, commitment, data_slice)
+    })
+}
+
+fn fetch_program_accounts_uncoalesced(
+    network: &Network,
+    program_id: &Pubkey,
+    filters: Option<Vec<Memcmp>>,
+    commitment: Option<CommitmentLevel>,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<Vec<(Pubkey, Account)>, ClientError> {
+    let program_accounts_config = program_accounts_config(filters, commitment, data_slice);
+
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_program_accounts_with_config(program_id, program_accounts_config.clone())
+    })
+}
+
+/// Same as fetch_program_accounts, but also returns the slot the scan was served at. The scan
+/// is requested with_context, which get_program_accounts_with_config can't parse, so the
+/// response is decoded here.
+pub fn fetch_program_accounts_with_slot(
+    network: &Network,
+    program_id: &Pubkey,
+    filters: Option<Vec<Memcmp>>,
+    commitment: CommitmentLevel,
+) -> Result<(Vec<(Pubkey, Account)>, u64), ClientError> {
+    let key = RpcCallKey::ProgramAccounts {
+        network: *network,
+        program_id: program_id.to_string(),
+        filters: format!("{:?}", filters),
+        commitment: format!("{:?}", commitment),
+        data_slice: format!("{:?}", None::<UiDataSliceConfig>),
+    };
+
+    PROGRAM_ACCOUNTS_WITH_SLOT_FLIGHTS.run(key, || {
+        fetch_program_accounts_with_slot_uncoalesced(network, program_id, filters, commitment)
+    })
+}
+
+fn fetch_program_accounts_with_slot_uncoalesced(
+    network: &Network,
+    program_id: &Pubkey,
+    filters: Option<Vec<Memcmp>>,
+    commitment: CommitmentLevel,
+) -> Result<(Vec<(Pubkey, Account)>, u64), ClientError> {
+    let mut config = program_accounts_config(filters, Some(commitment), None);
+    config.with_context = Some(true);
+
+    let response: RpcResponse<Vec<RpcKeyedAccount>> = with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.send(
+            RpcRequest::GetProgramAccounts,
+            json!([program_id.to_string(), config]),
+        )
+    })?;
+
+    let mut accounts = Vec::with_capacity(response.value.len());
+
+    for keyed in response.value.into_iter() {
+        let decoded = Pubkey::from_str(&keyed.pubkey)
+            .ok()
+            .zip(keyed.account.decode::<Account>());
+
+        match decoded {
+            Some(value) => accounts.push(value),
+            None => {
+                return Err(ClientErrorKind::Custom(format!(
+                    "InvalidKeyedAccount: pubkey={}",
+                    keyed.pubkey
+                ))
+                .into())
+            }
+        }
+    }
+
+    Ok((accounts, response.context.slot))
+}
+
+/// Builds the getProgramAccounts config shared by every program account scan
+fn program_accounts_config(
+    filters: Option<Vec<Memcmp>>,
+    commitment: Option<CommitmentLevel>,
+    data_slice: Option<UiDataSliceConfig>,
+) -> RpcProgramAccountsConfig {
     let mut filters_vec: Vec<RpcFilterType> = Vec::new();
 
     let conf = RpcAccountInfoConfig {
         encoding: Some(UiAccountEncoding::Base64), // enforce base64
-        data_slice: None,                          // enforce no data slice, subject to change
+        data_slice,
         commitment: if commitment.is_some() {
             Some(CommitmentConfig {
                 commitment: commitment.unwrap(),
@@ -86,7 +240,7 @@ pub fn fetch_program_accounts(
         }
     };
 
-    let program_accounts_config = RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
         filters: if filters_vec.len() > 0 {
             Some(filters_vec)
         } else {
@@ -94,7 +248,229 @@ pub fn fetch_program_accounts(
         },
         with_context: None,
         account_config: conf,
-    };
+    }
+}
+
+/// Fetches all quarries belonging to the specified rewarder. Returns a vector of
+/// (quarry pubkey, quarry account) tuples.
+pub fn fetch_quarries_for_rewarder(
+    network: &Network,
+    rewarder_pubkey: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>, ClientError> {
+    let filters = AccountFilters::new(AccountType::Quarry)
+        .equals("rewarder_key", &FieldValue::Pubkey(*rewarder_pubkey))
+        .expect("Quarry layout has a rewarder_key field")
+        .build();
+
+    fetch_program_accounts(
+        network,
+        &quarry_mine::ID,
+        Some(filters),
+        Some(CommitmentLevel::Confirmed),
+    )
+}
+
+/// Same as fetch_quarries_for_rewarder, but also returns the slot the scan was served at
+pub fn fetch_quarries_for_rewarder_with_slot(
+    network: &Network,
+    rewarder_pubkey: &Pubkey,
+) -> Result<(Vec<(Pubkey, Account)>, u64), ClientError> {
+    let filters = AccountFilters::new(AccountType::Quarry)
+        .equals("rewarder_key", &FieldValue::Pubkey(*rewarder_pubkey))
+        .expect("Quarry layout has a rewarder_key field")
+        .build();
+
+    fetch_program_accounts_with_slot(
+        network,
+        &quarry_mine::ID,
+        Some(filters),
+        CommitmentLevel::Confirmed,
+    )
+}
+
+/// Fetches all miners working on the specified quarry. Returns a vector of
+/// (miner pubkey, miner account) tuples.
+pub fn fetch_miners_for_quarry(
+    network: &Network,
+    quarry_pubkey: &Pubkey,
+) -> Result<Vec<(Pubkey, Account)>, ClientError> {
+    fetch_miners_for_quarry_sliced(network, quarry_pubkey, None)
+}
+
+/// Same as fetch_miners_for_quarry, but also returns the slot the scan was served at
+pub fn fetch_miners_for_quarry_with_slot(
+    network: &Network,
+    quarry_pubkey: &Pubkey,
+) -> Result<(Vec<(Pubkey, Account)>, u64), ClientError> {
+    let filters = AccountFilters::new(AccountType::Miner)
+        .equals("quarry_key", &FieldValue::Pubkey(*quarry_pubkey))
+        .expect("Miner layout has a quarry_key field")
+        .build();
+
+    fetch_program_accounts_with_slot(
+        network,
+        &quarry_mine::ID,
+        Some(filters),
+        CommitmentLevel::Confirmed,
+    )
+}
+
+/// Same as fetch_miners_for_quarry, but only downloads the `data_slice` byte range of each
+/// miner when one is given
+pub fn fetch_miners_for_quarry_sliced(
+    network: &Network,
+    quarry_pubkey: &Pubkey,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<Vec<(Pubkey, Account)>, ClientError> {
+    let filters = AccountFilters::new(AccountType::Miner)
+        .equals("quarry_key", &FieldValue::Pubkey(*quarry_pubkey))
+        .expect("Miner layout has a quarry_key field")
+        .build();
+
+    fetch_program_accounts_sliced(
+        network,
+        &quarry_mine::ID,
+        Some(filters),
+        Some(CommitmentLevel::Confirmed),
+        data_slice,
+    )
+}
+
+/// Fetches the current slot
+pub fn fetch_slot(network: &Network) -> Result<u64, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_slot()
+    })
+}
+
+/// Fetches a recent blockhash to attach to transactions built by warlock
+pub fn fetch_recent_blockhash(network: &Network) -> Result<Hash, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_recent_blockhash()
+            .map(|(blockhash, _fee_calculator)| blockhash)
+    })
+}
+
+/// Fetches several accounts in a single request. Accounts that don't exist are returned as None.
+pub fn fetch_multiple_accounts(
+    network: &Network,
+    account_pubkeys: &[Pubkey],
+) -> Result<Vec<Option<Account>>, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_multiple_accounts(account_pubkeys)
+    })
+}
+
+/// Fetches several accounts in a single request at the given commitment, along with the slot
+/// every one of them was read at
+pub fn fetch_multiple_accounts_with_slot(
+    network: &Network,
+    account_pubkeys: &[Pubkey],
+    commitment: Commitment,
+) -> Result<(Vec<Option<Account>>, u64), ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), commitment.fetch_config());
+        rpc.get_multiple_accounts_with_commitment(account_pubkeys, commitment.fetch_config())
+            .map(|response| (response.value, response.context.slot))
+    })
+}
+
+/// Simulates a transaction without verifying signatures and returns the post-simulation state
+/// of the requested accounts (base64 encoded) along with the program logs
+pub fn simulate_transaction(
+    network: &Network,
+    transaction: &Transaction,
+    account_pubkeys: &[Pubkey],
+) -> Result<RpcSimulateTransactionResult, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+
+        // Transactions from the /tx builders are unsigned and their blockhash may have
+        // expired by the time they are simulated
+        let conf = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: account_pubkeys.iter().map(|p| p.to_string()).collect(),
+            }),
+            ..RpcSimulateTransactionConfig::default()
+        };
+
+        rpc.simulate_transaction_with_config(transaction, conf)
+            .map(|response| response.value)
+    })
+}
+
+/// Fetches a confirmed transaction by signature, base64 encoded so it can be decoded locally
+pub fn fetch_transaction(
+    network: &Network,
+    signature: &Signature,
+) -> Result<EncodedConfirmedTransaction, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+        rpc.get_transaction(signature, UiTransactionEncoding::Base64)
+    })
+}
+
+/// Fetches confirmed transactions in the order of their signatures, running up to
+/// `concurrency` fetches at a time. Fails if any of them fails.
+pub fn fetch_transactions(
+    network: &Network,
+    signatures: &[Signature],
+    concurrency: usize,
+) -> Result<Vec<EncodedConfirmedTransaction>, ClientError> {
+    if signatures.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let concurrency = concurrency.max(1);
+    let chunk_size = (signatures.len() + concurrency - 1) / concurrency;
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = signatures
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|signature| fetch_transaction(network, signature))
+                        .collect::<Result<Vec<_>, ClientError>>()
+                })
+            })
+            .collect();
+
+        let mut transactions = Vec::with_capacity(signatures.len());
+        for worker in workers {
+            transactions.extend(worker.join().expect("Transaction fetch worker panicked")?);
+        }
+
+        Ok(transactions)
+    })
+}
+
+/// Fetches transaction signatures involving the given address, newest first. `before` and
+/// `until` are exclusive signature cursors and `limit` is capped at 1000 by the RPC node.
+pub fn fetch_signatures_for_address(
+    network: &Network,
+    address: &Pubkey,
+    before: Option<Signature>,
+    until: Option<Signature>,
+    limit: Option<usize>,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>, ClientError> {
+    with_failover(network, |url| {
+        let rpc = RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed());
+
+        let conf = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit,
+            commitment: Some(CommitmentConfig::confirmed()),
+        };
 
-    rpc.get_program_accounts_with_config(program_id, program_accounts_config)
+        rpc.get_signatures_for_address_with_config(address, conf)
+    })
 }
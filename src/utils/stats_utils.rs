@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// Gini coefficient of the given values, between 0 (perfectly equal) and 1 (a single holder
+/// owns everything). Returns 0 for an empty slice or when every value is zero.
+pub fn gini_coefficient(values: &[u64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    let n = sorted.len() as f64;
+    let total: f64 = sorted.iter().map(|v| *v as f64).sum();
+
+    if sorted.is_empty() || total == 0.0 {
+        return 0.0;
+    }
+
+    // G = (2 * sum(i * x_i)) / (n * sum(x_i)) - (n + 1) / n, with i starting at 1
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (i as f64 + 1.0) * (*v as f64))
+        .sum();
+
+    (2.0 * weighted) / (n * total) - (n + 1.0) / n
+}
+
+/// A histogram bucket covering balances in [lower_bound, upper_bound)
+#[derive(Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower_bound: u64,
+    /// None for the last, open ended bucket
+    pub upper_bound: Option<u64>,
+    pub count: u64,
+    pub total: u128,
+}
+
+/// Buckets the given values by order of magnitude (powers of 10), skipping zero values.
+/// Only buckets containing at least one value are returned.
+pub fn log10_histogram(values: &[u64]) -> Vec<HistogramBucket> {
+    // 10^0 through 10^19 covers the full u64 range
+    let mut buckets: Vec<HistogramBucket> = (0..20u32)
+        .map(|exp| HistogramBucket {
+            lower_bound: 10u64.pow(exp),
+            upper_bound: if exp < 19 {
+                Some(10u64.pow(exp + 1))
+            } else {
+                None
+            },
+            count: 0,
+            total: 0,
+        })
+        .collect();
+
+    for value in values.iter().filter(|v| **v > 0) {
+        let mut exp = 0;
+        let mut remaining = *value;
+        while remaining >= 10 {
+            remaining /= 10;
+            exp += 1;
+        }
+
+        let bucket = &mut buckets[exp];
+        bucket.count += 1;
+        bucket.total += *value as u128;
+    }
+
+    buckets.into_iter().filter(|b| b.count > 0).collect()
+}
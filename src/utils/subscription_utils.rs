@@ -0,0 +1,516 @@
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::pubsub_client::{
+    AccountSubscription, LogsSubscription, PubsubClient, PubsubClientError,
+};
+use solana_client::rpc_config::{
+    RpcAccountInfoConfig, RpcTransactionLogsConfig, RpcTransactionLogsFilter,
+};
+use solana_client::rpc_response::RpcLogsResponse;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task;
+use tracing::Level;
+
+use crate::utils::*;
+
+/// Number of updates a slow client may fall behind before it starts skipping them
+pub const SUBSCRIPTION_CHANNEL_CAPACITY: usize = 64;
+
+/// Most distinct accounts watched upstream at once. solana_client's PubsubClient opens a
+/// websocket and a thread per subscription, so accounts past this are refused rather than
+/// letting clients open connections without bound.
+pub const MAX_ACCOUNT_TOPICS: usize = 256;
+
+/// How often an upstream subscription checks whether it still has any listeners
+pub const SUBSCRIPTION_IDLE_CHECK: Duration = Duration::from_secs(5);
+
+/// Attempts made to fetch a transaction seen in the logs, which may briefly lag behind the
+/// logs notification on some RPC nodes
+pub const TRANSACTION_FETCH_ATTEMPTS: usize = 3;
+
+/// Delay between transaction fetch attempts
+pub const TRANSACTION_FETCH_RETRY_DELAY: Duration = Duration::from_millis(400);
+
+/// quarry_mine instructions that emit no event, so their logs alone don't show them happened
+pub const EVENTLESS_INSTRUCTIONS: [&str; 7] = [
+    "SetPauseAuthority",
+    "Pause",
+    "Unpause",
+    "TransferAuthority",
+    "AcceptAuthority",
+    "SetFamine",
+    "ExtractFees",
+];
+
+/// A change to a subscribed account, decoded into its wrapper
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AccountUpdate {
+    pub network: Network,
+    pub pubkey: Pubkey,
+    pub slot: u64,
+    /// None when the account was closed or isn't a quarry_mine account
+    pub account: Option<QuarryAccount>,
+}
+
+/// Why an account subscription couldn't be opened
+#[derive(Debug)]
+pub enum SubscribeError {
+    /// MAX_ACCOUNT_TOPICS accounts are already watched
+    TooManyTopics,
+    Pubsub(PubsubClientError),
+}
+
+/// Shares one upstream accountSubscribe per (network, pubkey) between every client watching
+/// that account. Each upstream subscription runs on its own thread and is torn down once its
+/// last listener goes away. At most MAX_ACCOUNT_TOPICS accounts are watched at once.
+///
+/// Accounts are subscribed one by one rather than through a single programSubscribe on
+/// quarry_mine. A program subscription would stream every miner write in the program to serve
+/// the handful of accounts clients actually watch, and each of those would have to be decoded
+/// just to be filtered out.
+#[derive(Default)]
+pub struct SubscriptionHub {
+    topics: Mutex<HashMap<(Network, Pubkey), broadcast::Sender<AccountUpdate>>>,
+}
+
+impl SubscriptionHub {
+    /// Returns a receiver of updates for the account, opening the upstream subscription if no
+    /// one else is watching it yet.
+    ///
+    /// The topic is registered before connecting, so concurrent subscribers share the pending
+    /// connection instead of opening their own. Connecting blocks, so it runs on the blocking
+    /// pool without holding the topics lock. If it fails the topic is removed again, which
+    /// closes the receivers handed out in the meantime.
+    pub async fn subscribe(
+        self: &Arc<Self>,
+        network: Network,
+        pubkey: Pubkey,
+    ) -> Result<broadcast::Receiver<AccountUpdate>, SubscribeError> {
+        let (sender, receiver) = {
+            let mut topics = self.topics.lock().unwrap();
+
+            if let Some(sender) = topics.get(&(network, pubkey)) {
+                return Ok(sender.subscribe());
+            }
+
+            if topics.len() >= MAX_ACCOUNT_TOPICS {
+                return Err(SubscribeError::TooManyTopics);
+            }
+
+            let (sender, receiver) = broadcast::channel(SUBSCRIPTION_CHANNEL_CAPACITY);
+            topics.insert((network, pubkey), sender.clone());
+            (sender, receiver)
+        };
+
+        let hub = Arc::clone(self);
+
+        // Runs to completion even if the caller goes away, so the topic never stays pending
+        let connected = task::spawn_blocking(move || {
+            let connection = with_ws_failover(&network, |url| {
+                PubsubClient::account_subscribe(
+                    url,
+                    &pubkey,
+                    Some(RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        data_slice: None,
+                    }),
+                )
+            });
+
+            let connection = match connection {
+                Ok(value) => value,
+                Err(e) => {
+                    hub.topics.lock().unwrap().remove(&(network, pubkey));
+                    return Err(e);
+                }
+            };
+
+            thread::spawn(move || hub.forward_updates(network, pubkey, connection, sender));
+            Ok(())
+        })
+        .await;
+
+        match connected {
+            Ok(Ok(())) => Ok(receiver),
+            Ok(Err(e)) => Err(SubscribeError::Pubsub(e)),
+            Err(e) => Err(SubscribeError::Pubsub(
+                PubsubClientError::UnexpectedMessageError(e.to_string()),
+            )),
+        }
+    }
+
+    /// Decodes and broadcasts account notifications until the upstream subscription drops or
+    /// nobody is listening anymore
+    fn forward_updates(
+        &self,
+        network: Network,
+        pubkey: Pubkey,
+        (mut subscription, updates): AccountSubscription,
+        sender: broadcast::Sender<AccountUpdate>,
+    ) {
+        loop {
+            match updates.recv_timeout(SUBSCRIPTION_IDLE_CHECK) {
+                Ok(response) => {
+                    let account = response
+                        .value
+                        .decode::<Account>()
+                        .as_ref()
+                        .and_then(QuarryAccount::decode);
+
+                    // Only fails when there are no receivers, which is handled below
+                    let _ = sender.send(AccountUpdate {
+                        network,
+                        pubkey,
+                        slot: response.context.slot,
+                        account,
+                    });
+                }
+
+                Err(RecvTimeoutError::Timeout) => {}
+
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Account subscription dropped: {}", pubkey);
+                    self.topics.lock().unwrap().remove(&(network, pubkey));
+                    break;
+                }
+            }
+
+            // New receivers are only handed out while holding the lock, so checking the
+            // count under it can't race with a fresh subscribe
+            let mut topics = self.topics.lock().unwrap();
+            if sender.receiver_count() == 0 {
+                topics.remove(&(network, pubkey));
+                break;
+            }
+        }
+
+        let _ = subscription.shutdown();
+    }
+}
+
+/// The quarry_mine instructions and events of a single landed transaction
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProgramActivity {
+    pub network: Network,
+    pub signature: String,
+    pub slot: u64,
+    /// Empty unless a listener asked for instructions or none of them emitted an event, see
+    /// ActivityHub
+    pub instructions: Vec<DecodedInstruction>,
+    pub events: Vec<DecodedEvent>,
+}
+
+impl ProgramActivity {
+    pub fn from_transaction(network: Network, tx: DecodedTransaction) -> Self {
+        Self {
+            network,
+            signature: tx.signature,
+            slot: tx.slot,
+            instructions: tx
+                .instructions
+                .into_iter()
+                .filter(|ix| ix.program_id == quarry_mine::ID)
+                .collect(),
+            events: tx
+                .events
+                .into_iter()
+                .filter(|e| e.program_id == quarry_mine::ID)
+                .collect(),
+        }
+    }
+
+    /// Builds the activity from a logs notification alone. Events are decoded from the logs,
+    /// instructions would need the transaction and are left empty.
+    pub fn from_logs(network: Network, slot: u64, logs: &RpcLogsResponse) -> Self {
+        Self {
+            network,
+            signature: logs.signature.clone(),
+            slot,
+            instructions: Vec::new(),
+            events: decode_events_from_logs(&logs.logs)
+                .into_iter()
+                .filter(|e| e.program_id == quarry_mine::ID)
+                .collect(),
+        }
+    }
+}
+
+/// Upstream logs subscription of a network and how many of its listeners need instructions
+struct ActivityTopic {
+    sender: broadcast::Sender<ProgramActivity>,
+    instruction_listeners: Arc<AtomicUsize>,
+}
+
+/// A listener of an ActivityHub topic. Dropping it stops the transaction fetches it asked for
+/// once no other listener needs them.
+pub struct ActivityReceiver {
+    pub receiver: broadcast::Receiver<ProgramActivity>,
+    instruction_listeners: Option<Arc<AtomicUsize>>,
+}
+
+impl Drop for ActivityReceiver {
+    fn drop(&mut self) {
+        if let Some(count) = self.instruction_listeners.take() {
+            count.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Shares one upstream logsSubscribe mentioning quarry_mine per network between every client
+/// tailing program activity. Events are decoded straight from the logs notification. Landed
+/// transactions are only fetched, once for all listeners, while some listener needs their
+/// instructions or when the logs show one of EVENTLESS_INSTRUCTIONS.
+#[derive(Default)]
+pub struct ActivityHub {
+    networks: Mutex<HashMap<Network, ActivityTopic>>,
+}
+
+impl ActivityHub {
+    /// Returns a receiver of decoded program activity, opening the upstream subscription if no
+    /// one else is tailing the network yet. Connects the same way SubscriptionHub::subscribe
+    /// does, off the runtime and outside the lock.
+    pub async fn subscribe(
+        self: &Arc<Self>,
+        network: Network,
+        with_instructions: bool,
+    ) -> Result<ActivityReceiver, PubsubClientError> {
+        let listener = |topic: &ActivityTopic| {
+            let instruction_listeners = if with_instructions {
+                topic.instruction_listeners.fetch_add(1, Ordering::SeqCst);
+                Some(Arc::clone(&topic.instruction_listeners))
+            } else {
+                None
+            };
+
+            ActivityReceiver {
+                receiver: topic.sender.subscribe(),
+                instruction_listeners,
+            }
+        };
+
+        let (sender, instruction_listeners, receiver) = {
+            let mut networks = self.networks.lock().unwrap();
+
+            if let Some(topic) = networks.get(&network) {
+                return Ok(listener(topic));
+            }
+
+            let (sender, _) = broadcast::channel(SUBSCRIPTION_CHANNEL_CAPACITY);
+            let topic = ActivityTopic {
+                sender: sender.clone(),
+                instruction_listeners: Arc::new(AtomicUsize::new(0)),
+            };
+            let receiver = listener(&topic);
+            let instruction_listeners = Arc::clone(&topic.instruction_listeners);
+            networks.insert(network, topic);
+            (sender, instruction_listeners, receiver)
+        };
+
+        let hub = Arc::clone(self);
+
+        // Runs to completion even if the caller goes away, so the topic never stays pending
+        let connected = task::spawn_blocking(move || {
+            let connection = with_ws_failover(&network, |url| {
+                PubsubClient::logs_subscribe(
+                    url,
+                    RpcTransactionLogsFilter::Mentions(vec![quarry_mine::ID.to_string()]),
+                    RpcTransactionLogsConfig {
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+            });
+
+            let connection = match connection {
+                Ok(value) => value,
+                Err(e) => {
+                    hub.networks.lock().unwrap().remove(&network);
+                    return Err(e);
+                }
+            };
+
+            thread::spawn(move || {
+                hub.forward_activity(network, connection, sender, instruction_listeners)
+            });
+            Ok(())
+        })
+        .await;
+
+        match connected {
+            Ok(Ok(())) => Ok(receiver),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(PubsubClientError::UnexpectedMessageError(e.to_string())),
+        }
+    }
+
+    /// Decodes and broadcasts the activity of logs notifications until the upstream
+    /// subscription drops or nobody is listening anymore
+    fn forward_activity(
+        &self,
+        network: Network,
+        (mut subscription, logs): LogsSubscription,
+        sender: broadcast::Sender<ProgramActivity>,
+        instruction_listeners: Arc<AtomicUsize>,
+    ) {
+        loop {
+            match logs.recv_timeout(SUBSCRIPTION_IDLE_CHECK) {
+                // Failed transactions don't change any state worth reporting
+                Ok(response) if response.value.err.is_none() => {
+                    let from_logs = || {
+                        ProgramActivity::from_logs(network, response.context.slot, &response.value)
+                    };
+
+                    let fetch = instruction_listeners.load(Ordering::SeqCst) > 0
+                        || mentions_eventless_instruction(&response.value.logs);
+
+                    let activity = if fetch {
+                        fetch_program_activity(network, &response.value.signature).unwrap_or_else(
+                            || {
+                                tracing::event!(
+                                    Level::ERROR,
+                                    "Streaming {} without instructions",
+                                    response.value.signature
+                                );
+                                from_logs()
+                            },
+                        )
+                    } else {
+                        from_logs()
+                    };
+
+                    let _ = sender.send(activity);
+                }
+
+                Ok(_response) => {}
+
+                Err(RecvTimeoutError::Timeout) => {}
+
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Logs subscription dropped");
+                    self.networks.lock().unwrap().remove(&network);
+                    break;
+                }
+            }
+
+            let mut networks = self.networks.lock().unwrap();
+            if sender.receiver_count() == 0 {
+                networks.remove(&network);
+                break;
+            }
+        }
+
+        let _ = subscription.shutdown();
+    }
+}
+
+/// Whether quarry_mine logged running one of EVENTLESS_INSTRUCTIONS. Only the
+/// "Program log: Instruction: <Name>" lines logged while quarry_mine is the innermost program
+/// count, so CPIs into other programs with the same instruction names are ignored.
+fn mentions_eventless_instruction(logs: &[String]) -> bool {
+    let quarry_mine = quarry_mine::ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+
+    for line in logs {
+        let mut words = line.split_whitespace();
+
+        match (words.next(), words.next(), words.next()) {
+            (Some("Program"), Some("log:"), Some("Instruction:")) => {
+                let name = words.next().unwrap_or_default();
+                if stack.last() == Some(&quarry_mine.as_str())
+                    && EVENTLESS_INSTRUCTIONS.contains(&name)
+                {
+                    return true;
+                }
+            }
+            (Some("Program"), Some(program), Some("invoke")) => stack.push(program),
+            (Some("Program"), Some(_), Some("success" | "failed:")) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Fetches and decodes a transaction seen in a logs notification, retrying while the RPC node
+/// catches up
+fn fetch_program_activity(network: Network, signature: &str) -> Option<ProgramActivity> {
+    let signature = Signature::from_str(signature).ok()?;
+
+    for attempt in 0..TRANSACTION_FETCH_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(TRANSACTION_FETCH_RETRY_DELAY);
+        }
+
+        if let Ok(confirmed) = fetch_transaction(&network, &signature) {
+            return DecodedTransaction::decode(&signature, &confirmed)
+                .map(|tx| ProgramActivity::from_transaction(network, tx));
+        }
+    }
+
+    tracing::event!(Level::ERROR, "Transaction fetch failed: {}", signature);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logs(lines: &[&str]) -> Vec<String> {
+        let quarry_mine = quarry_mine::ID.to_string();
+        lines
+            .iter()
+            .map(|line| line.replace("QMN", &quarry_mine))
+            .collect()
+    }
+
+    #[test]
+    fn pause_is_eventless() {
+        let logs = logs(&[
+            "Program QMN invoke [1]",
+            "Program log: Instruction: Pause",
+            "Program QMN consumed 4321 of 200000 compute units",
+            "Program QMN success",
+        ]);
+        assert!(mentions_eventless_instruction(&logs));
+    }
+
+    #[test]
+    fn stake_is_not_eventless() {
+        let logs = logs(&[
+            "Program QMN invoke [1]",
+            "Program log: Instruction: StakeTokens",
+            "Program Tokenkeg invoke [2]",
+            "Program log: Instruction: Transfer",
+            "Program Tokenkeg success",
+            "Program QMN success",
+        ]);
+        assert!(!mentions_eventless_instruction(&logs));
+    }
+
+    #[test]
+    fn other_programs_instructions_are_ignored() {
+        let logs = logs(&[
+            "Program Other invoke [1]",
+            "Program log: Instruction: Pause",
+            "Program QMN invoke [2]",
+            "Program log: Instruction: ClaimRewards",
+            "Program QMN success",
+            "Program log: Instruction: SetFamine",
+            "Program Other success",
+        ]);
+        assert!(!mentions_eventless_instruction(&logs));
+    }
+}
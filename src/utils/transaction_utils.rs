@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{hash::Hash, transaction::Transaction};
+
+use crate::utils::*;
+
+/// An unsigned transaction ready to be handed to a wallet, along with a readable summary of
+/// every instruction in it
+#[derive(Serialize, Deserialize)]
+pub struct UnsignedTransaction {
+    /// Base64 encoded, bincode serialized transaction with empty signatures
+    pub transaction: String,
+    pub fee_payer: Pubkey,
+    pub recent_blockhash: String,
+    pub instructions: Vec<NamedInstruction>,
+}
+
+impl UnsignedTransaction {
+    pub fn build(
+        instructions: Vec<NamedInstruction>,
+        fee_payer: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> Result<Self, bincode::Error> {
+        let ixs: Vec<_> = instructions.iter().map(|ix| ix.to_instruction()).collect();
+
+        let mut tx = Transaction::new_with_payer(&ixs, Some(fee_payer));
+        tx.message.recent_blockhash = recent_blockhash;
+
+        Ok(Self {
+            transaction: base64::encode(bincode::serialize(&tx)?),
+            fee_payer: *fee_payer,
+            recent_blockhash: recent_blockhash.to_string(),
+            instructions,
+        })
+    }
+}
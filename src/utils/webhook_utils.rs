@@ -0,0 +1,554 @@
+use hmac::{Hmac, Mac, NewMac};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use solana_program::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::Level;
+
+use crate::utils::*;
+
+/// Default file webhook registrations are persisted to, override with WEBHOOK_STORE_PATH
+pub const DEFAULT_WEBHOOK_STORE_PATH: &str = "webhooks.json";
+
+/// Default seconds between polls of watched accounts, override with WEBHOOK_POLL_SECONDS
+pub const DEFAULT_WEBHOOK_POLL_SECONDS: u64 = 30;
+
+/// Delivery attempts before a notification is dropped
+pub const WEBHOOK_DELIVERY_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled after every failed attempt
+pub const WEBHOOK_RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Maximum accounts per getMultipleAccounts call
+const MULTIPLE_ACCOUNTS_CHUNK: usize = 100;
+
+/// How a condition compares a decoded field
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConditionOperator {
+    /// The field differs from the previous poll
+    Changed,
+    Equals,
+    NotEquals,
+    LessThan,
+    GreaterThan,
+    /// The field is neither zero, false, null nor the default (all zero) pubkey
+    IsSet,
+    /// The field is a unix timestamp at most `value` seconds in the future
+    WithinSeconds,
+}
+
+/// A condition on a field of the decoded Quarry, Miner or Rewarder wrapper
+///
+/// {
+///     "field": "rewards_share", ~ wrapper field name, e.g. is_paused, famine_ts, balance
+///     "operator": "Changed",
+///     "value": null ~ compared against for every operator but Changed and IsSet
+/// }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WebhookCondition {
+    pub field: String,
+    pub operator: ConditionOperator,
+    #[serde(default)]
+    pub value: Value,
+}
+
+impl WebhookCondition {
+    /// Whether the condition fires between two polls. Conditions other than Changed are edge
+    /// triggered: they fire when they start holding, not on every poll they hold for.
+    pub fn fires(&self, previous: Option<&Value>, current: &Value, now: i64) -> bool {
+        let before = previous.map(|p| field_value(p, &self.field));
+        let after = field_value(current, &self.field);
+
+        match self.operator {
+            ConditionOperator::Changed => before.map(|b| b != after).unwrap_or(false),
+            _ => self.holds(&after, now) && !before.map(|b| self.holds(&b, now)).unwrap_or(false),
+        }
+    }
+
+    fn holds(&self, field: &Value, now: i64) -> bool {
+        match self.operator {
+            ConditionOperator::Changed => false,
+            ConditionOperator::Equals => *field == self.value,
+            ConditionOperator::NotEquals => *field != self.value,
+            ConditionOperator::LessThan => compare(field, &self.value) == Some(-1),
+            ConditionOperator::GreaterThan => compare(field, &self.value) == Some(1),
+            ConditionOperator::IsSet => is_set(field),
+            ConditionOperator::WithinSeconds => match (as_i128(field), as_i128(&self.value)) {
+                (Some(ts), Some(window)) => ts >= now as i128 && ts - (now as i128) <= window,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Looks up a field of a decoded wrapper, "a.b" reaches into nested objects
+fn field_value(account: &Value, field: &str) -> Value {
+    account
+        .pointer(&format!("/{}", field.replace('.', "/")))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+fn as_i128(value: &Value) -> Option<i128> {
+    value
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| value.as_u64().map(i128::from))
+}
+
+/// Compares two JSON numbers, exactly for integers. Returns -1, 0 or 1.
+fn compare(a: &Value, b: &Value) -> Option<i8> {
+    let ordering = match (as_i128(a), as_i128(b)) {
+        (Some(a), Some(b)) => a.partial_cmp(&b),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    };
+
+    ordering.map(|o| o as i8)
+}
+
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|n| n != 0.0).unwrap_or(true),
+        Value::String(s) => !s.is_empty(),
+        // Pubkeys serialize as byte arrays
+        Value::Array(items) => items.iter().any(|i| i.as_u64() != Some(0)),
+        Value::Object(_) => true,
+    }
+}
+
+/// A registered webhook watching a single Quarry, Miner or Rewarder account
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// Key for the HMAC-SHA256 signature sent in the X-Warlock-Signature header
+    pub secret: String,
+    pub network: Network,
+    pub pubkey: Pubkey,
+    pub conditions: Vec<WebhookCondition>,
+    pub created_at: i64,
+}
+
+/// Body POSTed to a webhook when one of its conditions fires
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebhookNotification {
+    pub webhook_id: String,
+    pub network: Network,
+    pub pubkey: Pubkey,
+    pub timestamp: i64,
+    pub condition: WebhookCondition,
+    pub before: Value,
+    pub after: Value,
+    /// The decoded account as of this poll
+    pub account: Value,
+}
+
+/// Webhook registrations, persisted as JSON so they survive restarts, plus the last decoded
+/// state of every watched account. That state is persisted next to the registrations so
+/// conditions don't fire again after a restart.
+pub struct WebhookStore {
+    path: PathBuf,
+    state_path: PathBuf,
+    webhooks: Mutex<HashMap<String, Webhook>>,
+    last_seen: Mutex<HashMap<String, Value>>,
+}
+
+impl WebhookStore {
+    /// Loads registrations from the store file and the last seen account states from the
+    /// state file next to it (webhooks.state.json for webhooks.json). Missing files start
+    /// empty. Any other read or parse failure is returned rather than starting empty, since
+    /// the next write would then overwrite the unreadable file.
+    pub fn load(path: PathBuf) -> std::io::Result<Self> {
+        let state_path = path.with_extension("state.json");
+
+        let webhooks: HashMap<String, Webhook> = read_json_or_default::<Vec<Webhook>>(&path)?
+            .into_iter()
+            .map(|w| (w.id.clone(), w))
+            .collect();
+
+        let mut last_seen: HashMap<String, Value> = read_json_or_default(&state_path)?;
+        last_seen.retain(|id, _| webhooks.contains_key(id));
+
+        tracing::event!(Level::INFO, "Loaded {} webhooks", webhooks.len());
+
+        Ok(Self {
+            path,
+            state_path,
+            webhooks: Mutex::new(webhooks),
+            last_seen: Mutex::new(last_seen),
+        })
+    }
+
+    pub fn list(&self) -> Vec<Webhook> {
+        let mut webhooks: Vec<Webhook> = self.webhooks.lock().unwrap().values().cloned().collect();
+        webhooks.sort_by_key(|w| w.created_at);
+        webhooks
+    }
+
+    pub fn insert(&self, webhook: Webhook) -> std::io::Result<()> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        webhooks.insert(webhook.id.clone(), webhook);
+        self.persist(&webhooks)
+    }
+
+    /// Removes the webhook if the secret is the one it was registered with. Returns whether
+    /// a webhook was removed, an unknown id and a wrong secret are not told apart.
+    pub fn remove(&self, id: &str, secret: &str) -> std::io::Result<bool> {
+        let mut webhooks = self.webhooks.lock().unwrap();
+        let authorized = webhooks
+            .get(id)
+            .map_or(false, |webhook| secrets_match(&webhook.secret, secret, id));
+
+        if !authorized {
+            return Ok(false);
+        }
+
+        let removed = webhooks.remove(id).is_some();
+        self.last_seen.lock().unwrap().remove(id);
+
+        if removed {
+            self.persist(&webhooks)?;
+        }
+
+        Ok(removed)
+    }
+
+    fn persist(&self, webhooks: &HashMap<String, Webhook>) -> std::io::Result<()> {
+        let mut list: Vec<&Webhook> = webhooks.values().collect();
+        list.sort_by_key(|w| w.created_at);
+
+        write_json_atomically(&self.path, &list)
+    }
+
+    fn persist_last_seen(&self, last_seen: &HashMap<String, Value>) -> std::io::Result<()> {
+        write_json_atomically(&self.state_path, last_seen)
+    }
+}
+
+/// Reads a JSON file, treating a missing file as the default value
+fn read_json_or_default<T: DeserializeOwned + Default>(path: &Path) -> std::io::Result<T> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes to a temporary file first so a crash never leaves a truncated file behind
+fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)
+}
+
+/// Generates a random 128-bit hex id
+pub fn generate_webhook_id() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+/// Hex encoded HMAC-SHA256 of the body under the webhook secret
+pub fn sign_webhook_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC accepts any key");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Compares secrets through their HMAC over the webhook id, so the comparison time doesn't
+/// depend on how much of the secret was guessed right
+fn secrets_match(expected: &str, given: &str, id: &str) -> bool {
+    sign_webhook_payload(expected, id.as_bytes()) == sign_webhook_payload(given, id.as_bytes())
+}
+
+/// Whether notifications may be sent to the url: it must be http(s) and every address its
+/// host resolves to must be public, so webhooks can't be pointed at loopback, private or
+/// link-local services next to warlock
+pub async fn webhook_url_allowed(url: &str) -> bool {
+    let url = match reqwest::Url::parse(url) {
+        Ok(value) => value,
+        Err(_e) => return false,
+    };
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return false;
+    }
+
+    let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => (host.trim_start_matches('[').trim_end_matches(']'), port),
+        _ => return false,
+    };
+
+    let addresses: Vec<IpAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(value) => value.map(|a| a.ip()).collect(),
+        Err(_e) => return false,
+    };
+
+    !addresses.is_empty() && addresses.iter().all(is_public_ip)
+}
+
+/// False for loopback, private, link-local, shared, unspecified and other non routable
+/// addresses
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let octets = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                // 100.64.0.0/10 carrier-grade NAT
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                // 0.0.0.0/8
+                || octets[0] == 0)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(&IpAddr::V4(v4));
+            }
+
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // fc00::/7 unique local
+                || (first & 0xfe00) == 0xfc00
+                // fe80::/10 link-local
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Polls every watched account forever, firing notifications for conditions that trip
+pub async fn watch_webhooks(store: Arc<WebhookStore>, interval: Duration) {
+    // Redirects could lead a delivery to a host webhook_url_allowed would have refused
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Webhook HTTP client builds with static settings");
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let webhooks = store.list();
+        if webhooks.is_empty() {
+            continue;
+        }
+
+        // RPC calls are blocking, keep them off the async workers
+        let accounts = match tokio::task::spawn_blocking({
+            let webhooks = webhooks.clone();
+            move || fetch_watched_accounts(&webhooks)
+        })
+        .await
+        {
+            Ok(value) => value,
+            Err(_e) => continue,
+        };
+
+        let now = current_unix_timestamp();
+        let mut last_seen = store.last_seen.lock().unwrap();
+        let mut state_changed = false;
+
+        for webhook in webhooks.iter() {
+            let current = match accounts.get(&(webhook.network, webhook.pubkey)) {
+                Some(value) => value.clone(),
+                // Skip accounts whose fetch failed rather than treating them as closed
+                None => continue,
+            };
+
+            let previous = last_seen.insert(webhook.id.clone(), current.clone());
+            state_changed |= previous.as_ref() != Some(&current);
+
+            for condition in webhook.conditions.iter() {
+                if !condition.fires(previous.as_ref(), &current, now) {
+                    continue;
+                }
+
+                let notification = WebhookNotification {
+                    webhook_id: webhook.id.clone(),
+                    network: webhook.network,
+                    pubkey: webhook.pubkey,
+                    timestamp: now,
+                    condition: condition.clone(),
+                    before: previous
+                        .as_ref()
+                        .map(|p| field_value(p, &condition.field))
+                        .unwrap_or(Value::Null),
+                    after: field_value(&current, &condition.field),
+                    account: current.clone(),
+                };
+
+                tokio::spawn(deliver_webhook(
+                    client.clone(),
+                    webhook.clone(),
+                    notification,
+                ));
+            }
+        }
+
+        if state_changed {
+            if let Err(e) = store.persist_last_seen(&last_seen) {
+                tracing::event!(Level::ERROR, "Webhook state persist failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Fetches and decodes every watched account, keyed by network and pubkey. Closed or
+/// undecodable accounts map to null.
+fn fetch_watched_accounts(webhooks: &[Webhook]) -> HashMap<(Network, Pubkey), Value> {
+    let mut by_network: HashMap<Network, Vec<Pubkey>> = HashMap::new();
+    for webhook in webhooks.iter() {
+        let keys = by_network.entry(webhook.network).or_default();
+        if !keys.contains(&webhook.pubkey) {
+            keys.push(webhook.pubkey);
+        }
+    }
+
+    let mut accounts = HashMap::new();
+
+    for (network, keys) in by_network.iter() {
+        for chunk in keys.chunks(MULTIPLE_ACCOUNTS_CHUNK) {
+            let fetched = match fetch_multiple_accounts(network, chunk) {
+                Ok(value) => value,
+                Err(_e) => {
+                    tracing::event!(Level::ERROR, "Webhook account fetch failed");
+                    continue;
+                }
+            };
+
+            for (pubkey, account) in chunk.iter().zip(fetched.iter()) {
+                let decoded = account
+                    .as_ref()
+                    .and_then(QuarryAccount::decode)
+                    .map(|a| a.to_value())
+                    .unwrap_or(Value::Null);
+
+                accounts.insert((*network, *pubkey), decoded);
+            }
+        }
+    }
+
+    accounts
+}
+
+/// POSTs a signed notification, retrying with exponential backoff on errors and non 2xx
+/// responses
+async fn deliver_webhook(
+    client: reqwest::Client,
+    webhook: Webhook,
+    notification: WebhookNotification,
+) {
+    let body = match serde_json::to_vec(&notification) {
+        Ok(value) => value,
+        Err(_e) => return,
+    };
+
+    // The host is checked again as it may resolve differently than at registration
+    if !webhook_url_allowed(&webhook.url).await {
+        tracing::event!(Level::ERROR, "Webhook url not allowed: {}", webhook.id);
+        return;
+    }
+
+    let signature = sign_webhook_payload(&webhook.secret, &body);
+    let mut delay = WEBHOOK_RETRY_BASE_DELAY;
+
+    for attempt in 1..=WEBHOOK_DELIVERY_ATTEMPTS {
+        let response = client
+            .post(&webhook.url)
+            .header("Content-Type", "application/json")
+            .header("X-Warlock-Webhook-Id", &webhook.id)
+            .header("X-Warlock-Signature", format!("sha256={}", signature))
+            .body(body.clone())
+            .send()
+            .await;
+
+        match response {
+            Ok(r) if r.status().is_success() => {
+                tracing::event!(Level::INFO, "Webhook delivered: {}", webhook.id);
+                return;
+            }
+            _ => {
+                tracing::event!(
+                    Level::ERROR,
+                    "Webhook delivery failed: {} - Attempt {}/{}",
+                    webhook.id,
+                    attempt,
+                    WEBHOOK_DELIVERY_ATTEMPTS
+                );
+            }
+        }
+
+        if attempt < WEBHOOK_DELIVERY_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ]
+        .iter()
+        {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn routable_addresses_are_public() {
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"].iter() {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback_and_non_http_urls_are_refused() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://[::1]/hook",
+            "ftp://1.1.1.1/hook",
+            "not a url",
+        ]
+        .iter()
+        {
+            assert!(!webhook_url_allowed(url).await, "{}", url);
+        }
+    }
+
+    #[test]
+    fn secrets_must_match_to_remove() {
+        assert!(secrets_match("s3cr3t", "s3cr3t", "id"));
+        assert!(!secrets_match("s3cr3t", "s3cr3", "id"));
+    }
+}